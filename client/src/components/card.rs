@@ -1,9 +1,22 @@
 use amethyst::ecs::{Component, DenseVecStorage};
+use shared::rules;
+use std::ops::{Deref, DerefMut};
 
-#[derive(Component, PartialEq, Eq, PartialOrd, Ord)]
+/// A card on the table, as described by the shared rules.
+#[derive(Component, Debug, Clone, PartialEq, Eq)]
 #[storage(DenseVecStorage)]
-pub struct Card {
-    pub name: String,
-    pub damage: u32,
-    //......
+pub struct Card(pub rules::Card);
+
+impl Deref for Card {
+    type Target = rules::Card;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for Card {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
 }
@@ -1,10 +1,22 @@
 use amethyst::ecs::{Component, DenseVecStorage};
+use shared::rules::CardPile;
+use std::ops::{Deref, DerefMut};
 
-use super::card::Card;
-
-#[derive(Component)]
+/// A hand or pile of cards on the table.
+#[derive(Component, Debug, Clone, Default)]
 #[storage(DenseVecStorage)]
-pub struct CardGroup {
-    pub cards_num: u32,
-    pub cards: Vec<Card>,
+pub struct CardGroup(pub CardPile);
+
+impl Deref for CardGroup {
+    type Target = CardPile;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for CardGroup {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
 }
@@ -1,16 +1,22 @@
 use amethyst::ecs::{Component, DenseVecStorage};
+use shared::rules;
+use std::ops::{Deref, DerefMut};
 
-use super::Card;
-
-#[derive(Component)]
+/// The equipment in front of a player, see `shared::rules::Equipment` for the slots.
+#[derive(Component, Debug, Clone, Default)]
 #[storage(DenseVecStorage)]
-pub struct Equipment {
-    // Different weapons have different effects
-    pub weapon: Card,
-    // Different armors have different effects
-    pub armor: Card,
-    // Increases your calculated distance from the enemy
-    pub offend_horse: Card,
-    // Reduces enemy's calculated distance from you
-    pub defend_horse: Card,
+pub struct Equipment(pub rules::Equipment);
+
+impl Deref for Equipment {
+    type Target = rules::Equipment;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for Equipment {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
 }
@@ -1,8 +1,9 @@
 use amethyst::{
-    ecs::prelude::World,
+    ecs::prelude::{Entities, Join, ReadStorage, World, WriteStorage},
     prelude::{Builder, WorldExt},
-    ui::{Anchor, UiImage, UiTransform},
+    ui::{Anchor, Interactable, UiImage, UiTransform},
 };
+use std::f32::consts::PI;
 
 use crate::{
     common::{DepthLayer, Pos},
@@ -11,7 +12,54 @@ use crate::{
     utilities::load::{load_transform, load_ui_text},
 };
 
-pub fn load_player(world: &mut World, name: String, num: usize) {
+/// Half the width and half the height of the ellipse the seats are placed on
+const TABLE_RADIUS: (f32, f32) = (450., 200.);
+/// The table is below the middle of the screen, the names and the turn frame are above
+const TABLE_CENTER_Y: f32 = -60.;
+
+/// Where the player on `seat` of a table with `seats` seats is drawn. The first seat is at
+/// the bottom and the others follow around the table.
+pub fn seat_position(seat: usize, seats: usize) -> (f32, f32) {
+    let angle = -PI / 2. + seat as f32 * 2. * PI / seats.max(1) as f32;
+    (
+        TABLE_RADIUS.0 * angle.cos(),
+        TABLE_CENTER_Y + TABLE_RADIUS.1 * angle.sin(),
+    )
+}
+
+/// The ui elements every drawn player is made of, see `load_player`.
+const PARTS: [&str; 5] = ["background", "name", "level", "record", "avater"];
+
+/// The part of the named player the ui element with the id is, if it is one. Only the ids
+/// `load_player` gives out match, so a player whose name starts with the name of another is
+/// left alone.
+fn part_of<'a>(id: &'a str, name: &str) -> Option<&'a str> {
+    let part = id
+        .strip_prefix("player_")?
+        .strip_prefix(name)?
+        .strip_prefix('_')?;
+    if PARTS.contains(&part) {
+        Some(part)
+    } else {
+        None
+    }
+}
+
+/// How far above the seat a part of a player is drawn.
+fn part_offset(part: &str) -> f32 {
+    match part {
+        "background" => 30.,
+        "name" => 140.,
+        "level" => -30.,
+        "record" => -60.,
+        "avater" => 60.,
+        _ => 0.,
+    }
+}
+
+/// Draw the player at `position`, see `seat_position`.
+pub fn load_player(world: &mut World, name: String, position: (f32, f32)) {
+    let (x, y) = position;
     let avater = {
         let assets = world.read_resource::<Assets>();
         assets.get_avatar(Avatar::Default)
@@ -28,8 +76,8 @@ pub fn load_player(world: &mut World, name: String, num: usize) {
         format!("player_{}_background", name),
         Anchor::Middle,
         Anchor::Middle,
-        -300. + num as f32 * 200.,
-        30.,
+        x,
+        y + part_offset("background"),
         130.,
         150.,
         250.,
@@ -46,8 +94,8 @@ pub fn load_player(world: &mut World, name: String, num: usize) {
         format!("player_{}_name", name),
         Anchor::Middle,
         Anchor::Middle,
-        -300. + num as f32 * 200.,
-        140.,
+        x,
+        y + part_offset("name"),
         200.,
         145.,
         18.,
@@ -64,8 +112,8 @@ pub fn load_player(world: &mut World, name: String, num: usize) {
         format!("player_{}_level", name),
         Anchor::Middle,
         Anchor::Middle,
-        -300. + num as f32 * 200.,
-        -30.,
+        x,
+        y + part_offset("level"),
         200.,
         145.,
         18.,
@@ -79,11 +127,11 @@ pub fn load_player(world: &mut World, name: String, num: usize) {
 
     let ui_record = load_ui_text(world, "战绩： 0胜0败".to_owned());
     let ui_reocrd_transfrom = UiTransform::new(
-        format!("player_{}_level", name),
+        format!("player_{}_record", name),
         Anchor::Middle,
         Anchor::Middle,
-        -300. + num as f32 * 200.,
-        -60.,
+        x,
+        y + part_offset("record"),
         200.,
         145.,
         18.,
@@ -116,8 +164,8 @@ pub fn load_player(world: &mut World, name: String, num: usize) {
         format!("player_{}_avater", name),
         Anchor::Middle,
         Anchor::Middle,
-        -300. + num as f32 * 200.,
-        60.,
+        x,
+        y + part_offset("avater"),
         200.,
         145.,
         145.,
@@ -131,3 +179,62 @@ pub fn load_player(world: &mut World, name: String, num: usize) {
         .with(ui_transfrom)
         .build();
 }
+
+/// Move the drawn player to another seat.
+pub fn move_player(world: &mut World, name: &str, position: (f32, f32)) {
+    world.exec(|mut transforms: WriteStorage<'_, UiTransform>| {
+        for transform in (&mut transforms).join() {
+            let offset = part_of(&transform.id, name).map(part_offset);
+            if let Some(offset) = offset {
+                transform.local_x = position.0;
+                transform.local_y = position.1 + offset;
+            }
+        }
+    });
+}
+
+/// Take away the player who left the table.
+pub fn remove_player(world: &mut World, name: &str) {
+    remove_ui(world, |id| part_of(id, name).is_some());
+}
+
+/// Delete the ui elements whose id matches.
+fn remove_ui(world: &mut World, matches: impl Fn(&str) -> bool) {
+    world.exec(
+        |(entities, transforms): (Entities<'_>, ReadStorage<'_, UiTransform>)| {
+            for (entity, transform) in (&entities, &transforms).join() {
+                if matches(&transform.id) {
+                    if let Err(e) = entities.delete(entity) {
+                        log::warn!("Failed to remove {}: {:?}", transform.id, e);
+                    }
+                }
+            }
+        },
+    );
+}
+
+/// Put a button under every seat, clicking it asks the server for the seat. The buttons of
+/// a table with another number of seats are taken away.
+pub fn load_seats(world: &mut World, seats: usize) {
+    remove_ui(world, |id| id.starts_with("seat_"));
+    for seat in 0..seats {
+        let (x, y) = seat_position(seat, seats);
+        let text = load_ui_text(world, format!("{}号座位", seat + 1));
+        let transform = UiTransform::new(
+            format!("seat_{}", seat),
+            Anchor::Middle,
+            Anchor::Middle,
+            x,
+            y - 90.,
+            200.,
+            145.,
+            18.,
+        );
+        world
+            .create_entity()
+            .with(text)
+            .with(transform)
+            .with(Interactable)
+            .build();
+    }
+}
@@ -1,4 +1,4 @@
-use std::net::{SocketAddr, TcpListener, UdpSocket};
+use std::net::{SocketAddr, UdpSocket};
 
 use crate::{resources::Music, systems::message::MessageBundle};
 use amethyst::{
@@ -15,7 +15,7 @@ use amethyst::{
     utils::fps_counter::FpsCounterBundle,
     Result,
 };
-use shared::clientinfo::ClientInfo;
+use shared::{clientinfo::ClientInfo, transport::Transport};
 use states::loading::LoadingState;
 use structopt::StructOpt;
 
@@ -52,8 +52,17 @@ pub struct Client {
     #[structopt(long, default_value = "client")]
     pub name: String,
 
-    #[structopt(long, default_value = "2000")]
+    /// The local udp port, a free one is picked by default. Not used with tcp.
+    #[structopt(long, default_value = "0")]
     pub port: u16,
+
+    /// Keep human-readable JSON on the wire instead of the compact binary format.
+    #[structopt(long)]
+    pub debug_wire: bool,
+
+    /// Talk to the server over udp datagrams or a tcp stream, must match the server.
+    #[structopt(long, default_value = "udp", possible_values = &["udp", "tcp"])]
+    pub transport: Transport,
 }
 
 impl Client {
@@ -63,21 +72,29 @@ impl Client {
 
     pub fn run(self) -> Result<()> {
         let server_info = ServerInfoResource { addr: self.url };
-        let client_info = ClientInfo {
-            name: self.name,
-            port: self.port,
-        };
         amethyst::start_logger(Default::default());
 
         let display_config_path = get_config_dir().join("display.ron");
 
-        let addr = SocketAddr::from(([0, 0, 0, 0], self.port));
-        let listener_addr = SocketAddr::from(([0, 0, 0, 0], self.port));
-
-        let listener = TcpListener::bind(listener_addr)?;
-        listener.set_nonblocking(true)?;
-        let socket = UdpSocket::bind(addr).unwrap();
-        let _ = socket.set_nonblocking(true);
+        let socket = match self.transport {
+            Transport::Udp => {
+                let addr = SocketAddr::from(([0, 0, 0, 0], self.port));
+                let socket = UdpSocket::bind(addr).unwrap();
+                let _ = socket.set_nonblocking(true);
+                Some(socket)
+            }
+            Transport::Tcp => None,
+        };
+        // The server answers on the address our messages come from, so the port is only
+        // informative
+        let port = match &socket {
+            Some(socket) => socket.local_addr()?.port(),
+            None => self.port,
+        };
+        let client_info = ClientInfo {
+            name: self.name,
+            port,
+        };
 
         let game_data = GameDataBuilder::default()
             .with_bundle(TransformBundle::new())?
@@ -89,7 +106,7 @@ impl Client {
                 server_info,
                 client_info,
                 socket,
-                listener,
+                self.debug_wire,
             ))?
             .with_bundle(
                 RenderingBundle::<DefaultBackend>::new()
@@ -13,6 +13,20 @@ pub enum CharacterType {
     Kokoro,
 }
 
+impl CharacterType {
+    /// The name of the character in the card database.
+    pub fn name(self) -> Option<&'static str> {
+        match self {
+            CharacterType::NotFound => None,
+            CharacterType::Alice => Some("Alice"),
+            CharacterType::Cirno => Some("Cirno"),
+            CharacterType::Flandre => Some("Flandre"),
+            CharacterType::Kanako => Some("Kanako"),
+            CharacterType::Kokoro => Some("Kokoro"),
+        }
+    }
+}
+
 impl Default for CharacterType {
     fn default() -> Self {
         CharacterType::NotFound
@@ -0,0 +1,25 @@
+use shared::handshake::Welcome;
+
+/// The state of the connection to the server. It is written by the `MessageSystem` and
+/// read by the states, which leave for the `ErrorScreen` once the connection failed.
+#[derive(Debug, Clone)]
+pub enum ConnectionStatus {
+    /// No handshake has been started yet.
+    Offline,
+    /// The `Hello` was sent, waiting for the answer of the server.
+    Connecting,
+    /// The server accepted the handshake.
+    Connected(Welcome),
+    /// The server rejected the handshake or could not be understood.
+    Failed(String),
+    /// The server stopped answering, trying to take the old seat again.
+    Reconnecting,
+    /// The server stopped answering and did not take the client back.
+    Lost,
+}
+
+impl Default for ConnectionStatus {
+    fn default() -> Self {
+        ConnectionStatus::Offline
+    }
+}
@@ -56,6 +56,10 @@ pub enum UiType {
     PauseMenu,
     /// For the character selection screen before the game starts
     CharacterSelection,
+    /// Choosing the cards to discard at the end of the turn
+    Discard,
+    /// Answering another player's card, or saving a dying player
+    Response,
     /// The welcome screen
     Welcome,
 }
@@ -3,8 +3,10 @@ mod audio;
 mod avatar;
 mod character;
 mod config;
+mod connection;
 mod context;
 mod gui;
+mod table;
 mod userdata;
 
 pub use self::assets::*;
@@ -12,6 +14,8 @@ pub use self::audio::*;
 pub use self::avatar::*;
 pub use self::character::*;
 pub use self::config::*;
+pub use self::connection::*;
 pub use self::context::Context;
 pub use self::gui::*;
+pub use self::table::{Countdown, Table};
 pub use self::userdata::*;
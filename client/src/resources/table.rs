@@ -0,0 +1,129 @@
+use shared::{
+    msg::Prompt,
+    rules::{ActionError, CardDatabase, CardId, GameEvent, GameState, Phase},
+};
+use std::time::Instant;
+
+/// The time the player the game waits for has left, as the server told it last.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Countdown {
+    pub player: String,
+    pub seconds: u32,
+    /// Whether the time is taken from the time bank of the player
+    pub bank: bool,
+}
+
+/// What the client knows about the running game. It is written by the `MessageSystem`
+/// from the events of the server and read by the states.
+#[derive(Debug, Clone, Default)]
+pub struct Table {
+    /// What this client may see of the game: seats, equipment, who is alive and the own
+    /// hand. The other hands are only counted, see `PlayerState::hidden_cards`, and the
+    /// draw pile is unknown. `None` until a game started.
+    pub state: Option<GameState>,
+    /// The player whose turn it is
+    pub current: Option<String>,
+    pub phase: Option<Phase>,
+    /// Why the server refused the last action of this client
+    pub rejected: Option<ActionError>,
+    /// The player the game waits for to respond
+    pub waiting: Option<String>,
+    /// What this client is asked to respond to, and until when
+    pub prompt: Option<Prompt>,
+    pub respond_by: Option<Instant>,
+    pub countdown: Option<Countdown>,
+    /// The winners, once the game is over
+    pub winners: Option<Vec<String>>,
+    /// The number of the last event applied, later copies of it are dropped
+    pub seq: Option<u64>,
+}
+
+impl Table {
+    /// Whether a game is running.
+    pub fn started(&self) -> bool {
+        self.state.is_some() && self.winners.is_none()
+    }
+
+    pub fn finished(&self) -> bool {
+        self.winners.is_some()
+    }
+
+    /// Apply the event numbered `seq` unless it was applied already.
+    pub fn receive(&mut self, seq: u64, event: &GameEvent, database: &CardDatabase) {
+        if let Some(last) = self.seq {
+            if seq <= last {
+                return;
+            }
+        }
+        self.apply(event, database);
+        self.seq = Some(seq);
+    }
+
+    /// Apply the next event of the game, in the order the server sent them.
+    pub fn apply(&mut self, event: &GameEvent, database: &CardDatabase) {
+        if let GameEvent::GameStarted { players, .. } = event {
+            // The shuffle is only known to the server
+            *self = Table {
+                state: Some(GameState::new(players.clone(), vec![], 0)),
+                ..Table::default()
+            };
+        }
+        if let Some(state) = self.state.as_mut() {
+            state.apply(event, database);
+        }
+        match event {
+            GameEvent::TurnStarted { player, .. } => {
+                self.current = Some(player.clone());
+                self.rejected = None;
+            }
+            GameEvent::PhaseChanged { phase, .. } => {
+                self.phase = Some(*phase);
+                // The server starts another clock if the game waits for somebody
+                self.countdown = None;
+                // The discard is over once the turn goes on, made or timed out
+                self.waiting = None;
+                if let Some(Prompt::Discard { .. }) = self.prompt {
+                    self.prompt = None;
+                    self.respond_by = None;
+                }
+            }
+            GameEvent::ResponseRequested { player }
+            | GameEvent::DiscardRequested { player, .. } => self.waiting = Some(player.clone()),
+            GameEvent::CardsDiscarded { .. } => {
+                if let Some(Prompt::Discard { .. }) = self.prompt {
+                    self.prompt = None;
+                    self.respond_by = None;
+                }
+            }
+            GameEvent::Responded { .. } => {
+                self.waiting = None;
+                self.countdown = None;
+                self.prompt = None;
+                self.respond_by = None;
+            }
+            GameEvent::GameOver { winners, .. } => self.winners = Some(winners.clone()),
+            _ => {}
+        }
+    }
+
+    /// The players the named player can aim the card at, to highlight them.
+    pub fn possible_targets(
+        &self,
+        database: &CardDatabase,
+        player: &str,
+        card: CardId,
+    ) -> Vec<String> {
+        let (state, card) = match (self.state.as_ref(), database.card(card)) {
+            (Some(state), Some(card)) => (state, card),
+            _ => return vec![],
+        };
+        match state.seat_of(player) {
+            Some(seat) => state
+                .possible_targets(database, seat, &card)
+                .into_iter()
+                .map(|target| state.players[target].name.clone())
+                .collect(),
+            None => vec![],
+        }
+    }
+}
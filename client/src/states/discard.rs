@@ -0,0 +1,210 @@
+use amethyst::{
+    ecs::Entity,
+    input::is_close_requested,
+    prelude::*,
+    shrev::EventChannel,
+    ui::{Anchor, Interactable, UiEvent, UiEventType, UiFinder, UiText, UiTransform},
+};
+
+use crate::{
+    resources::{Table, UiHandles, UiType},
+    systems::message::PlayerRequest,
+    utilities::load::load_ui_text,
+};
+use shared::{
+    msg::{GameAction, Prompt},
+    rules::{CardDatabase, CardId},
+};
+
+/// Text color of the cards that are not chosen
+const NORMAL: [f32; 4] = [1., 1., 1., 1.];
+/// Text color of the cards chosen to discard
+const CHOSEN: [f32; 4] = [0.9, 0.2, 0.2, 1.];
+/// Horizontal distance between the cards
+const CARD_SPACING: f32 = 100.;
+
+/// Pushed over the game when the server asks this client to discard down to the hand limit.
+/// The cards in hand are listed, clicking one chooses it or takes it back, and the choice is
+/// sent once exactly as many cards as asked for are chosen. The state goes away once the
+/// server discarded the cards, chosen or because the time was up, and lets the player choose
+/// again if the server refused the choice.
+#[derive(Debug, Default)]
+pub struct DiscardState {
+    count: usize,
+    hand: Vec<CardId>,
+    ui_root: Option<Entity>,
+    title: Option<Entity>,
+    confirm: Option<Entity>,
+    cards: Vec<(Entity, CardId)>,
+    chosen: Vec<CardId>,
+    // The choice was sent, the server has not answered yet
+    sent: bool,
+}
+
+impl DiscardState {
+    pub fn new(count: u32, hand: Vec<CardId>) -> Self {
+        Self {
+            count: count as usize,
+            hand,
+            ..Self::default()
+        }
+    }
+
+    fn init_ui(&mut self, data: &mut StateData<'_, GameData<'_, '_>>) {
+        self.ui_root = UiHandles::add_ui(UiType::Discard, data.world);
+        let names: Vec<String> = {
+            let database = data.world.read_resource::<CardDatabase>();
+            self.hand
+                .iter()
+                .map(|id| match database.card(*id) {
+                    Some(card) => card.to_string(),
+                    None => id.to_string(),
+                })
+                .collect()
+        };
+        let left = -(self.hand.len() as f32 - 1.) * CARD_SPACING / 2.;
+        for (index, (id, name)) in self.hand.iter().zip(names).enumerate() {
+            let text = load_ui_text(data.world, name);
+            let transform = UiTransform::new(
+                format!("discard_card_{}", id),
+                Anchor::Middle,
+                Anchor::Middle,
+                left + index as f32 * CARD_SPACING,
+                0.,
+                3.,
+                CARD_SPACING - 10.,
+                40.,
+            );
+            let entity = data
+                .world
+                .create_entity()
+                .with(text)
+                .with(transform)
+                .with(Interactable)
+                .build();
+            self.cards.push((entity, *id));
+        }
+        // invoke a world update to finish creating our ui entities
+        data.data.update(data.world);
+    }
+
+    /// Choose the card, or take it back.
+    fn toggle(&mut self, world: &mut World, entity: Entity, card: CardId) {
+        let chosen = match self.chosen.iter().position(|c| *c == card) {
+            Some(index) => {
+                self.chosen.remove(index);
+                false
+            }
+            None => {
+                self.chosen.push(card);
+                true
+            }
+        };
+        if let Some(text) = world.write_storage::<UiText>().get_mut(entity) {
+            text.color = if chosen { CHOSEN } else { NORMAL };
+        }
+        self.show_title(world);
+    }
+
+    fn show_title(&self, world: &mut World) {
+        let mut ui_text = world.write_storage::<UiText>();
+        if let Some(title) = self.title.and_then(|e| ui_text.get_mut(e)) {
+            title.text = format!("弃牌：{}/{}", self.chosen.len(), self.count);
+        }
+    }
+}
+
+impl SimpleState for DiscardState {
+    fn on_start(&mut self, mut data: StateData<'_, GameData<'_, '_>>) {
+        self.init_ui(&mut data);
+    }
+
+    fn on_stop(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+        if let Some(root_entity) = self.ui_root.take() {
+            data.world
+                .delete_entity(root_entity)
+                .expect("Failed to remove the discard selection");
+        }
+        for (entity, _) in self.cards.drain(..) {
+            data.world
+                .delete_entity(entity)
+                .expect("Failed to remove a card of the discard selection");
+        }
+        self.title = None;
+        self.confirm = None;
+        self.chosen.clear();
+        self.sent = false;
+    }
+
+    fn handle_event(&mut self, data: StateData<'_, GameData>, event: StateEvent) -> SimpleTrans {
+        match event {
+            StateEvent::Window(event) if is_close_requested(&event) => {
+                log::info!("[Trans::Quit] Quitting Application!");
+                Trans::Quit
+            }
+            StateEvent::Ui(UiEvent {
+                event_type: UiEventType::Click,
+                target,
+            }) => {
+                if self.sent {
+                    return Trans::None;
+                }
+                if Some(target) == self.confirm {
+                    if self.chosen.len() != self.count {
+                        log::info!("Choose {} cards to discard", self.count);
+                        return Trans::None;
+                    }
+                    log::info!("Discard {:?}", self.chosen);
+                    let action = GameAction::Discard {
+                        cards: self.chosen.clone(),
+                    };
+                    data.world
+                        .write_resource::<EventChannel<PlayerRequest>>()
+                        .single_write(PlayerRequest::Game(action));
+                    // The prompt stays until the server discarded the cards
+                    data.world.write_resource::<Table>().rejected = None;
+                    self.sent = true;
+                    return Trans::None;
+                }
+                if let Some((entity, card)) = self.cards.iter().find(|(e, _)| *e == target) {
+                    let (entity, card) = (*entity, *card);
+                    self.toggle(data.world, entity, card);
+                }
+                Trans::None
+            }
+            _ => Trans::None,
+        }
+    }
+
+    fn update(&mut self, data: &mut StateData<'_, GameData>) -> SimpleTrans {
+        let rejected = {
+            let table = data.world.read_resource::<Table>();
+            let asked = match &table.prompt {
+                Some(Prompt::Discard { count, hand }) => {
+                    *count as usize == self.count && *hand == self.hand
+                }
+                _ => false,
+            };
+            if !asked {
+                // Discarded, or asked again with another hand the game shows anew
+                log::info!("[Trans::Pop] The discard is over!");
+                return Trans::Pop;
+            }
+            table.rejected.clone()
+        };
+        if let (true, Some(reason)) = (self.sent, rejected) {
+            log::warn!("The discard was refused: {}", reason);
+            self.sent = false;
+        }
+        // this cannot happen in 'on_start', as the entity might not be fully
+        // initialized/registered/created yet.
+        if self.confirm.is_none() {
+            data.world.exec(|finder: UiFinder<'_>| {
+                self.title = finder.find("discard_title");
+                self.confirm = finder.find("discard_confirm");
+            });
+            self.show_title(data.world);
+        }
+        Trans::None
+    }
+}
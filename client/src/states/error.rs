@@ -0,0 +1,96 @@
+use amethyst::{
+    ecs::Entity,
+    input::{is_close_requested, is_key_down, is_mouse_button_down},
+    prelude::*,
+    ui::{Anchor, UiTransform},
+    winit::{MouseButton, VirtualKeyCode},
+};
+
+use super::menu::MainMenu;
+use crate::{resources::ConnectionStatus, utilities::load::load_ui_text};
+
+/// Shown when the connection to the server could not be established, e.g. because the server
+/// rejected the handshake, or when it was lost later on. Pressing escape or clicking anywhere
+/// returns to the main menu.
+#[derive(Debug, Default)]
+pub struct ErrorScreen {
+    reason: String,
+    ui_root: Option<Entity>,
+}
+
+impl ErrorScreen {
+    pub fn new(reason: String) -> Self {
+        Self {
+            reason: format!("连接失败：{}", reason),
+            ui_root: None,
+        }
+    }
+
+    /// The server stopped answering.
+    pub fn lost() -> Self {
+        Self {
+            reason: "与服务器的连接已断开".to_string(),
+            ui_root: None,
+        }
+    }
+
+    fn init_ui(&mut self, world: &mut World) {
+        let ui_reason = load_ui_text(world, self.reason.clone());
+        let ui_reason_transform = UiTransform::new(
+            "error_reason".to_string(),
+            Anchor::Middle,
+            Anchor::Middle,
+            0.,
+            0.,
+            1.,
+            1000.,
+            50.,
+        );
+
+        self.ui_root = Some(
+            world
+                .create_entity()
+                .with(ui_reason)
+                .with(ui_reason_transform)
+                .build(),
+        );
+    }
+}
+
+impl SimpleState for ErrorScreen {
+    fn on_start(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+        log::error!("[Connection] {}", self.reason);
+        self.init_ui(data.world);
+    }
+
+    fn handle_event(&mut self, _: StateData<'_, GameData>, event: StateEvent) -> SimpleTrans {
+        match &event {
+            StateEvent::Window(event) => {
+                if is_close_requested(event) {
+                    log::info!("[Trans::Quit] Quitting Application!");
+                    Trans::Quit
+                } else if is_key_down(event, VirtualKeyCode::Escape)
+                    || is_mouse_button_down(event, MouseButton::Left)
+                {
+                    log::info!("[Trans::Switch] Switching to MainMenu!");
+                    Trans::Switch(Box::new(MainMenu::default()))
+                } else {
+                    Trans::None
+                }
+            }
+            _ => Trans::None,
+        }
+    }
+
+    fn on_stop(&mut self, data: StateData<GameData>) {
+        if let Some(root_entity) = self.ui_root {
+            data.world
+                .delete_entity(root_entity)
+                .expect("Failed to remove ErrorScreen");
+        }
+
+        self.ui_root = None;
+        // Allow a new attempt from the main menu
+        *data.world.write_resource::<ConnectionStatus>() = ConnectionStatus::Offline;
+    }
+}
@@ -3,14 +3,45 @@ use amethyst::{
     ecs::Entity,
     input::{is_close_requested, is_key_down},
     prelude::*,
-    ui::{UiFinder, UiText},
+    shrev::EventChannel,
+    ui::{Anchor, Interactable, UiEvent, UiEventType, UiFinder, UiText, UiTransform},
     utils::fps_counter::FpsCounter,
     winit::VirtualKeyCode,
 };
 
-use crate::resources::{UiHandles, UiType};
+use crate::{
+    resources::{ConnectionStatus, Countdown, Table, UiHandles, UiType},
+    systems::message::PlayerRequest,
+    utilities::load::load_ui_text,
+};
+use shared::{
+    clientinfo::ClientInfo,
+    msg::{GameAction, Prompt},
+    rules::{CardDatabase, CardId},
+};
+
+use super::{
+    discard::DiscardState, error::ErrorScreen, lobby::Lobby, pause::PauseMenuState,
+    response::ResponseState,
+};
+
+/// Color of the countdown while the time limit runs
+const TIMER_NORMAL: [f32; 4] = [1., 1., 1., 1.];
+/// Color of the countdown once the time bank is used
+const TIMER_BANK: [f32; 4] = [0.9, 0.2, 0.2, 1.];
+/// Text color of the cards and players that are not chosen
+const NORMAL: [f32; 4] = [1., 1., 1., 1.];
+/// Text color of the chosen card
+const CHOSEN: [f32; 4] = [0.9, 0.2, 0.2, 1.];
+/// Text color of the players the chosen card can be aimed at
+const REACHABLE: [f32; 4] = [0.2, 0.9, 0.2, 1.];
+/// Text color of the players the chosen card cannot be aimed at
+const UNREACHABLE: [f32; 4] = [0.5, 0.5, 0.5, 1.];
+/// Horizontal distance between the cards in hand
+const CARD_SPACING: f32 = 90.;
+/// Vertical distance between the players in the list
+const PLAYER_SPACING: f32 = 26.;
 
-use super::pause::PauseMenuState;
 /// Main 'Game' state. Actually, it is mostly similar to the ui/main.rs content-wise.
 /// The main differences include the added 'paused' field in the state, which is toggled when
 /// 'pausing'.
@@ -26,6 +57,17 @@ pub struct Game {
     // A reference to the FPS display, which we want to interact with
     fps_display: Option<Entity>,
     player_display: Option<Entity>,
+    // The countdown drawn on the turn frame
+    timer_display: Option<Entity>,
+    // The cards in hand along the bottom, clicking one chooses it
+    hand: Vec<(Entity, CardId)>,
+    // Everybody at the table, listed at the top left
+    players: Vec<(Entity, String)>,
+    // The card in hand that was clicked last
+    chosen: Option<CardId>,
+    // Plays the chosen card without a target
+    play: Option<Entity>,
+    end_phase: Option<Entity>,
 }
 
 impl Game {
@@ -34,6 +76,139 @@ impl Game {
         // invoke a world update to finish creating our ui entities
         data.data.update(data.world);
     }
+
+    /// Show the cards in hand anew once they changed.
+    fn show_hand(&mut self, world: &mut World, hand: &[CardId]) {
+        if self.hand.iter().map(|(_, id)| id).eq(hand.iter()) {
+            return;
+        }
+        for (entity, _) in self.hand.drain(..) {
+            if let Err(e) = world.delete_entity(entity) {
+                log::warn!("Failed to remove a card in hand: {:?}", e);
+            }
+        }
+        if let Some(chosen) = self.chosen {
+            if !hand.contains(&chosen) {
+                self.chosen = None;
+            }
+        }
+        let names: Vec<String> = {
+            let database = world.read_resource::<CardDatabase>();
+            hand.iter()
+                .map(|id| match database.card(*id) {
+                    Some(card) => card.to_string(),
+                    None => id.to_string(),
+                })
+                .collect()
+        };
+        let left = -(hand.len() as f32 - 1.) * CARD_SPACING / 2.;
+        for (index, (id, name)) in hand.iter().zip(names).enumerate() {
+            let text = load_ui_text(world, name);
+            let transform = UiTransform::new(
+                format!("game_card_{}", id),
+                Anchor::BottomMiddle,
+                Anchor::Middle,
+                left + index as f32 * CARD_SPACING,
+                16.,
+                3.,
+                CARD_SPACING - 5.,
+                24.,
+            );
+            let entity = world
+                .create_entity()
+                .with(text)
+                .with(transform)
+                .with(Interactable)
+                .build();
+            self.hand.push((entity, *id));
+        }
+    }
+
+    /// List the players of the game in turn order, once it is known who plays.
+    fn show_players(&mut self, world: &mut World, players: &[String]) {
+        if !self.players.is_empty() {
+            return;
+        }
+        for (index, name) in players.iter().enumerate() {
+            let text = load_ui_text(world, name.clone());
+            let transform = UiTransform::new(
+                format!("game_player_{}", index),
+                Anchor::TopLeft,
+                Anchor::Middle,
+                100.,
+                -70. - index as f32 * PLAYER_SPACING,
+                3.,
+                160.,
+                24.,
+            );
+            let entity = world
+                .create_entity()
+                .with(text)
+                .with(transform)
+                .with(Interactable)
+                .build();
+            self.players.push((entity, name.clone()));
+        }
+    }
+
+    /// Ask the server to carry out the action, it tells everybody what happened.
+    fn act(&mut self, world: &mut World, action: GameAction) {
+        log::info!("Act: {:?}", action);
+        world
+            .write_resource::<EventChannel<PlayerRequest>>()
+            .single_write(PlayerRequest::Game(action));
+        world.write_resource::<Table>().rejected = None;
+        self.chosen = None;
+        self.highlight(world);
+    }
+
+    /// Mark the chosen card, and the players it can be aimed at with the same range checks
+    /// the server makes.
+    fn highlight(&self, world: &mut World) {
+        let reachable = self.chosen.map(|card| {
+            let table = world.read_resource::<Table>();
+            let database = world.read_resource::<CardDatabase>();
+            let me = world.read_resource::<ClientInfo>();
+            table.possible_targets(&database, &me.name, card)
+        });
+        let mut ui_text = world.write_storage::<UiText>();
+        for (entity, card) in &self.hand {
+            if let Some(text) = ui_text.get_mut(*entity) {
+                text.color = if self.chosen == Some(*card) {
+                    CHOSEN
+                } else {
+                    NORMAL
+                };
+            }
+        }
+        for (entity, name) in &self.players {
+            if let Some(text) = ui_text.get_mut(*entity) {
+                text.color = match &reachable {
+                    None => NORMAL,
+                    Some(reachable) if reachable.contains(name) => REACHABLE,
+                    Some(_) => UNREACHABLE,
+                };
+            }
+        }
+    }
+}
+
+/// The players of the game and the cards in the hand of this client, if a game runs.
+fn seen_by(world: &World) -> Option<(Vec<String>, Vec<CardId>)> {
+    let table = world.read_resource::<Table>();
+    let me = world.read_resource::<ClientInfo>();
+    let state = table.state.as_ref()?;
+    let players = state.players.iter().map(|p| p.name.clone()).collect();
+    let hand = match state.seat_of(&me.name) {
+        Some(seat) => state.players[seat]
+            .hand
+            .cards()
+            .iter()
+            .map(|c| c.id)
+            .collect(),
+        None => vec![],
+    };
+    Some((players, hand))
 }
 
 impl SimpleState for Game {
@@ -66,12 +241,28 @@ impl SimpleState for Game {
                 .expect("Failed to remove Game Screen");
         }
 
+        let shown: Vec<Entity> = self
+            .hand
+            .drain(..)
+            .map(|(entity, _)| entity)
+            .chain(self.players.drain(..).map(|(entity, _)| entity))
+            .collect();
+        for entity in shown {
+            data.world
+                .delete_entity(entity)
+                .expect("Failed to remove the cards and players of the game");
+        }
+
         self.ui_root = None;
         self.fps_display = None;
         self.player_display = None;
+        self.timer_display = None;
+        self.chosen = None;
+        self.play = None;
+        self.end_phase = None;
     }
 
-    fn handle_event(&mut self, _: StateData<'_, GameData>, event: StateEvent) -> SimpleTrans {
+    fn handle_event(&mut self, data: StateData<'_, GameData>, event: StateEvent) -> SimpleTrans {
         match &event {
             StateEvent::Window(event) => {
                 if is_close_requested(event) {
@@ -84,6 +275,32 @@ impl SimpleState for Game {
                     Trans::None
                 }
             }
+            StateEvent::Ui(UiEvent {
+                event_type: UiEventType::Click,
+                target,
+            }) => {
+                // Clicking the chosen card again takes it back
+                if let Some((_, card)) = self.hand.iter().find(|(e, _)| e == target) {
+                    self.chosen = match self.chosen {
+                        Some(chosen) if chosen == *card => None,
+                        _ => Some(*card),
+                    };
+                    self.highlight(data.world);
+                } else if Some(*target) == self.end_phase {
+                    self.act(data.world, GameAction::EndPhase);
+                } else if let Some(card) = self.chosen {
+                    // The chosen card goes to the clicked player, or to nobody in particular
+                    let targets = match self.players.iter().find(|(e, _)| e == target) {
+                        Some((_, name)) => Some(vec![name.clone()]),
+                        None if Some(*target) == self.play => Some(vec![]),
+                        None => None,
+                    };
+                    if let Some(targets) = targets {
+                        self.act(data.world, GameAction::PlayCard { card, targets });
+                    }
+                }
+                Trans::None
+            }
             StateEvent::Ui(_ui_event) => {
                 // log::info!(
                 //     "[HANDLE_EVENT] You just interacted with a ui element: {:?}",
@@ -101,6 +318,36 @@ impl SimpleState for Game {
     fn update(&mut self, state_data: &mut StateData<'_, GameData>) -> SimpleTrans {
         let StateData { world, .. } = state_data;
 
+        if let ConnectionStatus::Lost = *world.read_resource::<ConnectionStatus>() {
+            log::info!("[Trans::Switch] Switching to ErrorScreen!");
+            return Trans::Switch(Box::new(ErrorScreen::lost()));
+        }
+
+        if let Some((players, hand)) = seen_by(world) {
+            self.show_players(world, &players);
+            self.show_hand(world, &hand);
+            self.highlight(world);
+        }
+
+        let table = world.read_resource::<Table>();
+        if table.finished() {
+            log::info!("The game is over, the winners are {:?}", table.winners);
+            log::info!("[Trans::Switch] Switching to Lobby!");
+            return Trans::Switch(Box::new(Lobby::default()));
+        }
+        match &table.prompt {
+            Some(Prompt::Discard { count, hand }) => {
+                log::info!("[Trans::Push] Choosing {} cards to discard!", count);
+                return Trans::Push(Box::new(DiscardState::new(*count, hand.clone())));
+            }
+            Some(prompt) => {
+                log::info!("[Trans::Push] Responding to {:?}!", prompt);
+                let state = ResponseState::new(prompt.clone(), table.respond_by);
+                return Trans::Push(Box::new(state));
+            }
+            None => {}
+        }
+
         // this cannot happen in 'on_start', as the entity might not be fully
         // initialized/registered/created yet.
         if self.fps_display.is_none() {
@@ -111,6 +358,19 @@ impl SimpleState for Game {
             });
         }
 
+        if self.play.is_none() {
+            world.exec(|finder: UiFinder<'_>| {
+                self.play = finder.find("game_play");
+                self.end_phase = finder.find("game_end_phase");
+            });
+        }
+
+        if self.timer_display.is_none() {
+            world.exec(|finder: UiFinder<'_>| {
+                self.timer_display = finder.find("game_timer");
+            });
+        }
+
         if self.player_display.is_none() {
             world.exec(|finder: UiFinder<'_>| {
                 if let Some(entity) = finder.find("flandre") {
@@ -124,6 +384,23 @@ impl SimpleState for Game {
         if !self.paused {
             let mut ui_text = world.write_storage::<UiText>();
 
+            if let Some(timer) = self
+                .timer_display
+                .and_then(|entity| ui_text.get_mut(entity))
+            {
+                match &table.countdown {
+                    Some(Countdown {
+                        player,
+                        seconds,
+                        bank,
+                    }) => {
+                        timer.text = format!("{}：{}秒", player, seconds);
+                        timer.color = if *bank { TIMER_BANK } else { TIMER_NORMAL };
+                    }
+                    None => timer.text.clear(),
+                }
+            }
+
             if let Some(fps_display) = self.fps_display.and_then(|entity| ui_text.get_mut(entity)) {
                 if world.read_resource::<Time>().frame_number() % 20 == 0 && !self.paused {
                     let fps = world.read_resource::<FpsCounter>().sampled_fps();
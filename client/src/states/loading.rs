@@ -18,6 +18,7 @@ use amethyst::{
 use crate::states::welcome::WelcomeScreen;
 use crate::utilities::{files::get_config_dir, loading_config::LoadingConfig};
 use amethyst::audio::{AudioSink, OggFormat};
+use shared::rules::CardDatabase;
 
 /// This state is briefly active when the game is first started up. It loads all assets used in the
 /// entire game and then switches to the main menu state.
@@ -161,10 +162,23 @@ fn load_loading_config() -> LoadingConfig {
     })
 }
 
+/// Load the `CardDatabase` from file. The server loads the same file, so both sides agree on
+/// what every card does.
+fn load_card_database() -> CardDatabase {
+    CardDatabase::load(&get_config_dir().join("cards.ron")).unwrap_or_else(|error| {
+        error!(
+            "Failed to load the card database! Falling back to an empty one. Error: {}",
+            error
+        );
+        CardDatabase::default()
+    })
+}
+
 /// Load various configuration resources from their respective files and insert them into the World
 /// as resources.
 fn load_configs(world: &mut World) {
     world.insert(load_audio_settings());
+    world.insert(load_card_database());
 
     world.insert(if get_user_cache_file().is_file() {
         UserCache::load(get_user_cache_file()).unwrap_or_else(|error| {
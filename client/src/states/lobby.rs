@@ -1,20 +1,22 @@
 use amethyst::{
     core::Time,
-    ecs::Entity,
+    ecs::{Entity, WriteStorage},
     input::{is_close_requested, is_key_down},
     prelude::*,
-    ui::{UiEvent, UiEventType, UiFinder, UiText},
+    shrev::EventChannel,
+    ui::{UiEvent, UiEventType, UiFinder, UiText, UiTransform},
     utils::fps_counter::FpsCounter,
     winit::VirtualKeyCode,
 };
 
-use super::pause::PauseMenuState;
+use super::{error::ErrorScreen, game::Game, pause::PauseMenuState, select_character::SelectState};
 use crate::{
     common::camera::*,
     // entities::player::load_player,
-    resources::{UiHandles, UiType},
-    states::select_character::SelectState,
+    resources::{ConnectionStatus, Table, UiHandles, UiType},
+    systems::message::PlayerRequest,
 };
+use shared::msg::LobbyMessage;
 
 /// Main 'Game' state. Actually, it is mostly similar to the ui/main.rs content-wise.
 /// The main differences include the added 'paused' field in the state, which is toggled when
@@ -33,6 +35,12 @@ pub struct Lobby {
     fps_display: Option<Entity>,
     // A button to start game
     start_game: Option<Entity>,
+    // A button to choose the character for the next game
+    choose_character: Option<Entity>,
+    // Whether this player told the server that it is ready to start
+    prepared: bool,
+    // Shows where the player is, it is updated with the server name after the handshake
+    location_label: Option<Entity>,
 }
 
 impl Lobby {
@@ -46,6 +54,13 @@ impl Lobby {
     // }
 }
 
+/// The seat whose button was clicked, see `load_seats`.
+fn clicked_seat(world: &World, target: Entity) -> Option<usize> {
+    let transforms = world.read_storage::<UiTransform>();
+    let id = &transforms.get(target)?.id;
+    id.strip_prefix("seat_")?.parse().ok()
+}
+
 impl SimpleState for Lobby {
     fn on_start(&mut self, mut data: StateData<'_, GameData<'_, '_>>) {
         // let StateData { mut world, .. } = data;
@@ -77,9 +92,10 @@ impl SimpleState for Lobby {
 
         self.ui_root = None;
         self.fps_display = None;
+        self.location_label = None;
     }
 
-    fn handle_event(&mut self, _: StateData<'_, GameData>, event: StateEvent) -> SimpleTrans {
+    fn handle_event(&mut self, data: StateData<'_, GameData>, event: StateEvent) -> SimpleTrans {
         match event {
             StateEvent::Window(event) => {
                 if is_close_requested(&event) {
@@ -97,8 +113,26 @@ impl SimpleState for Lobby {
                 target,
             }) => {
                 if Some(target) == self.start_game {
-                    log::info!("[Trans::Switch] Switching to Select!");
+                    // The game starts once every player in the lobby is ready
+                    self.prepared = !self.prepared;
+                    log::info!("Prepared: {}", self.prepared);
+                    let body = if self.prepared {
+                        LobbyMessage::Prepare
+                    } else {
+                        LobbyMessage::CancelPrepare
+                    };
+                    data.world
+                        .write_resource::<EventChannel<PlayerRequest>>()
+                        .single_write(PlayerRequest::Lobby(body));
+                } else if Some(target) == self.choose_character {
+                    log::info!("[Trans::Push] Choosing a character!");
                     return Trans::Push(Box::new(SelectState::default()));
+                } else if let Some(seat) = clicked_seat(data.world, target) {
+                    // The server swaps with whoever sits there and tells everybody
+                    log::info!("Sit down on seat {}", seat);
+                    data.world
+                        .write_resource::<EventChannel<PlayerRequest>>()
+                        .single_write(PlayerRequest::Lobby(LobbyMessage::ChooseSeat { seat }));
                 }
                 Trans::None
             }
@@ -113,6 +147,42 @@ impl SimpleState for Lobby {
     fn update(&mut self, state_data: &mut StateData<'_, GameData>) -> SimpleTrans {
         let StateData { world, .. } = state_data;
 
+        let status = world.read_resource::<ConnectionStatus>().clone();
+        match status {
+            ConnectionStatus::Failed(reason) => {
+                log::info!("[Trans::Switch] Switching to ErrorScreen!");
+                return Trans::Switch(Box::new(ErrorScreen::new(reason)));
+            }
+            ConnectionStatus::Lost => {
+                log::info!("[Trans::Switch] Switching to ErrorScreen!");
+                return Trans::Switch(Box::new(ErrorScreen::lost()));
+            }
+            ConnectionStatus::Reconnecting => {
+                // Forget the label, so it is updated again once the server took us back
+                if let Some(label) = self.location_label.take() {
+                    if let Some(text) = world.write_storage::<UiText>().get_mut(label) {
+                        text.text = "正在重新连接……".to_string();
+                    }
+                }
+            }
+            ConnectionStatus::Connected(welcome) if self.location_label.is_none() => {
+                world.exec(
+                    |(finder, mut ui_text): (UiFinder<'_>, WriteStorage<'_, UiText>)| {
+                        self.location_label = finder.find("lobby_label");
+                        if let Some(label) = self.location_label.and_then(|e| ui_text.get_mut(e)) {
+                            label.text = format!("当前位置：{}", welcome.server_name);
+                        }
+                    },
+                );
+            }
+            _ => {}
+        }
+
+        if world.read_resource::<Table>().started() {
+            log::info!("[Trans::Switch] Switching to Game!");
+            return Trans::Switch(Box::new(Game::default()));
+        }
+
         // this cannot happen in 'on_start', as the entity might not be fully
         // initialized/registered/created yet.
         if self.fps_display.is_none() {
@@ -131,6 +201,12 @@ impl SimpleState for Lobby {
             });
         }
 
+        if self.choose_character.is_none() {
+            world.exec(|finder: UiFinder<'_>| {
+                self.choose_character = finder.find("lobby_character");
+            });
+        }
+
         // it is important that the 'paused' field is actually pausing your game.
         // Make sure to also pause your running systems.
         if !self.paused {
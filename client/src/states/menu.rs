@@ -1,7 +1,6 @@
 use amethyst::{
     ecs::Entity,
     input::{is_close_requested, is_key_down},
-    network::simulation::TransportResource,
     prelude::*,
     ui::{UiEvent, UiEventType, UiFinder},
     winit::VirtualKeyCode,
@@ -10,13 +9,8 @@ use log::info;
 
 use super::{credits::CreditsScreen, welcome::WelcomeScreen};
 use crate::{
-    resources::{UiHandles, UiType},
+    resources::{ConnectionStatus, UiHandles, UiType},
     states::lobby::Lobby,
-    systems::message::ServerInfoResource,
-};
-use shared::{
-    clientinfo::ClientInfo,
-    utilities::msg::{Message, MessageLayer, TransMessage},
 };
 
 const BUTTON_START: &str = "start";
@@ -39,27 +33,11 @@ impl MainMenu {
         self.menu_buttons.load_buttons(data.world);
     }
 
-    /// The player should connect to the server when he enters the lobby, and
-    /// here the player should send his information to the server to facilitate
-    /// the server loading the players in the lobby.
+    /// The player should connect to the server when he enters the lobby. This asks the
+    /// `MessageSystem` to start the handshake, once the server accepts it the player's
+    /// information is sent to the server to facilitate the server loading the players in the lobby.
     fn init_connection(&mut self, data: StateData<'_, GameData<'_, '_>>) {
-        let world = data.world;
-        let client = world.fetch_mut::<ClientInfo>().clone();
-        let server = world.fetch::<ServerInfoResource>();
-        let mut net = world.fetch_mut::<TransportResource>();
-        let msg = Message::new(
-            client,
-            shared::msg::MessageType::EnterLobby,
-            "I want to connect to the server".to_owned(),
-        );
-
-        let trans_message = TransMessage::construct(MessageLayer::Connection, msg);
-
-        // FIXME: unwrap()
-        net.send(
-            server.get_addr(),
-            trans_message.serialize().unwrap().as_bytes(),
-        );
+        *data.world.fetch_mut::<ConnectionStatus>() = ConnectionStatus::Connecting;
     }
 }
 
@@ -1,8 +1,11 @@
 pub mod credits;
+pub mod discard;
+pub mod error;
 pub mod game;
 pub mod loading;
 pub mod lobby;
 pub mod menu;
 pub mod pause;
+pub mod response;
 pub mod select_character;
 pub mod welcome;
@@ -0,0 +1,202 @@
+use amethyst::{
+    ecs::Entity,
+    input::is_close_requested,
+    prelude::*,
+    shrev::EventChannel,
+    ui::{Anchor, Interactable, UiEvent, UiEventType, UiFinder, UiText, UiTransform},
+};
+use std::time::Instant;
+
+use crate::{
+    resources::{Table, UiHandles, UiType},
+    systems::message::PlayerRequest,
+    utilities::load::load_ui_text,
+};
+use shared::{
+    clientinfo::ClientInfo,
+    msg::{GameAction, Prompt},
+    rules::{CardDatabase, CardId},
+};
+
+/// Horizontal distance between the cards
+const CARD_SPACING: f32 = 100.;
+
+/// Pushed over the game when the server asks this client to cancel a card or to save a
+/// dying player. The cards in hand are listed, clicking one answers with it, and passing
+/// answers without a card. The state goes away once the server took the answer, or the time
+/// was up, and lets the player answer again if the server refused the card.
+#[derive(Debug)]
+pub struct ResponseState {
+    prompt: Prompt,
+    // Tells this prompt from the next one, which may ask the same again
+    asked_at: Option<Instant>,
+    ui_root: Option<Entity>,
+    title: Option<Entity>,
+    pass: Option<Entity>,
+    cards: Vec<(Entity, CardId)>,
+    // The answer was sent, the server has not taken it yet
+    sent: bool,
+}
+
+impl ResponseState {
+    pub fn new(prompt: Prompt, asked_at: Option<Instant>) -> Self {
+        Self {
+            prompt,
+            asked_at,
+            ui_root: None,
+            title: None,
+            pass: None,
+            cards: vec![],
+            sent: false,
+        }
+    }
+
+    fn init_ui(&mut self, data: &mut StateData<'_, GameData<'_, '_>>) {
+        self.ui_root = UiHandles::add_ui(UiType::Response, data.world);
+        let hand: Vec<(CardId, String)> = {
+            let table = data.world.read_resource::<Table>();
+            let me = data.world.read_resource::<ClientInfo>();
+            let state = table.state.as_ref();
+            match state.and_then(|s| s.seat_of(&me.name).map(|seat| &s.players[seat])) {
+                Some(player) => player
+                    .hand
+                    .cards()
+                    .iter()
+                    .map(|card| (card.id, card.to_string()))
+                    .collect(),
+                None => vec![],
+            }
+        };
+        let left = -(hand.len() as f32 - 1.) * CARD_SPACING / 2.;
+        for (index, (id, name)) in hand.into_iter().enumerate() {
+            let text = load_ui_text(data.world, name);
+            let transform = UiTransform::new(
+                format!("response_card_{}", id),
+                Anchor::Middle,
+                Anchor::Middle,
+                left + index as f32 * CARD_SPACING,
+                0.,
+                3.,
+                CARD_SPACING - 10.,
+                40.,
+            );
+            let entity = data
+                .world
+                .create_entity()
+                .with(text)
+                .with(transform)
+                .with(Interactable)
+                .build();
+            self.cards.push((entity, id));
+        }
+        // invoke a world update to finish creating our ui entities
+        data.data.update(data.world);
+    }
+
+    /// What the player is asked, with the name of the card to cancel.
+    fn describe(&self, database: &CardDatabase) -> String {
+        match &self.prompt {
+            Prompt::Cancel {
+                card,
+                source,
+                target,
+                ..
+            } => {
+                let card = match database.card(*card) {
+                    Some(card) => card.name,
+                    None => card.to_string(),
+                };
+                format!("{}对{}使用了{}", source, target, card)
+            }
+            Prompt::Rescue { dying } => format!("{}濒死，是否救援", dying),
+            Prompt::Discard { count, .. } => format!("弃牌：{}", count),
+        }
+    }
+
+    /// Send the answer, the prompt stays until the server took it.
+    fn answer(&mut self, world: &mut World, card: Option<CardId>) {
+        log::info!("Respond with {:?}", card);
+        world
+            .write_resource::<EventChannel<PlayerRequest>>()
+            .single_write(PlayerRequest::Game(GameAction::Respond { card }));
+        world.write_resource::<Table>().rejected = None;
+        self.sent = true;
+    }
+}
+
+impl SimpleState for ResponseState {
+    fn on_start(&mut self, mut data: StateData<'_, GameData<'_, '_>>) {
+        self.init_ui(&mut data);
+    }
+
+    fn on_stop(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+        if let Some(root_entity) = self.ui_root.take() {
+            data.world
+                .delete_entity(root_entity)
+                .expect("Failed to remove the response");
+        }
+        for (entity, _) in self.cards.drain(..) {
+            data.world
+                .delete_entity(entity)
+                .expect("Failed to remove a card of the response");
+        }
+        self.title = None;
+        self.pass = None;
+        self.sent = false;
+    }
+
+    fn handle_event(&mut self, data: StateData<'_, GameData>, event: StateEvent) -> SimpleTrans {
+        match event {
+            StateEvent::Window(event) if is_close_requested(&event) => {
+                log::info!("[Trans::Quit] Quitting Application!");
+                Trans::Quit
+            }
+            StateEvent::Ui(UiEvent {
+                event_type: UiEventType::Click,
+                target,
+            }) => {
+                if self.sent {
+                    return Trans::None;
+                }
+                if Some(target) == self.pass {
+                    self.answer(data.world, None);
+                } else if let Some((_, card)) = self.cards.iter().find(|(e, _)| *e == target) {
+                    let card = *card;
+                    self.answer(data.world, Some(card));
+                }
+                Trans::None
+            }
+            _ => Trans::None,
+        }
+    }
+
+    fn update(&mut self, data: &mut StateData<'_, GameData>) -> SimpleTrans {
+        let rejected = {
+            let table = data.world.read_resource::<Table>();
+            if table.prompt.as_ref() != Some(&self.prompt) || table.respond_by != self.asked_at {
+                // Answered, or asked anew, which the game shows anew
+                log::info!("[Trans::Pop] The response is over!");
+                return Trans::Pop;
+            }
+            table.rejected.clone()
+        };
+        if let (true, Some(reason)) = (self.sent, rejected) {
+            log::warn!("The answer was refused: {}", reason);
+            self.sent = false;
+        }
+        // this cannot happen in 'on_start', as the entity might not be fully
+        // initialized/registered/created yet.
+        if self.pass.is_none() {
+            data.world.exec(|finder: UiFinder<'_>| {
+                self.title = finder.find("response_title");
+                self.pass = finder.find("response_pass");
+            });
+            let text = self.describe(&data.world.read_resource::<CardDatabase>());
+            let mut ui_text = data.world.write_storage::<UiText>();
+            if let Some(title) = self.title.and_then(|e| ui_text.get_mut(e)) {
+                title.text = text;
+            }
+        }
+        Trans::None
+    }
+}
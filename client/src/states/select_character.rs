@@ -1,10 +1,33 @@
-use amethyst::{ecs::Entity, GameData, SimpleState, StateData};
+use amethyst::{
+    ecs::Entity,
+    input::{is_close_requested, is_key_down},
+    prelude::*,
+    shrev::EventChannel,
+    ui::{UiEvent, UiEventType, UiFinder},
+    winit::VirtualKeyCode,
+};
 
-use crate::resources::{UiHandles, UiType};
+use crate::{
+    resources::{CharacterType, UiHandles, UiType},
+    systems::message::PlayerRequest,
+};
+use shared::msg::LobbyMessage;
 
+/// The characters that can be chosen, with the id of their button.
+const CHOICES: [(CharacterType, &str); 5] = [
+    (CharacterType::Alice, "select_alice"),
+    (CharacterType::Cirno, "select_cirno"),
+    (CharacterType::Flandre, "select_flandre"),
+    (CharacterType::Kanako, "select_kanako"),
+    (CharacterType::Kokoro, "select_kokoro"),
+];
+
+/// Pushed over the lobby to choose the character for the next game. The choice is sent to
+/// the server, which gives the character its health and skills when the game starts.
 #[derive(Debug, Default)]
 pub struct SelectState {
     ui_root: Option<Entity>,
+    buttons: Vec<(Entity, CharacterType)>,
 }
 
 impl SelectState {
@@ -24,5 +47,59 @@ impl SimpleState for SelectState {
 
     fn on_resume(&mut self, _data: amethyst::StateData<'_, amethyst::GameData<'_, '_>>) {}
 
-    fn on_stop(&mut self, _data: amethyst::StateData<'_, amethyst::GameData<'_, '_>>) {}
+    fn on_stop(&mut self, data: amethyst::StateData<'_, amethyst::GameData<'_, '_>>) {
+        if let Some(root_entity) = self.ui_root.take() {
+            data.world
+                .delete_entity(root_entity)
+                .expect("Failed to remove the character selection");
+        }
+        self.buttons.clear();
+    }
+
+    fn handle_event(&mut self, data: StateData<'_, GameData>, event: StateEvent) -> SimpleTrans {
+        match event {
+            StateEvent::Window(event) => {
+                if is_close_requested(&event) {
+                    log::info!("[Trans::Quit] Quitting Application!");
+                    Trans::Quit
+                } else if is_key_down(&event, VirtualKeyCode::Escape) {
+                    log::info!("[Trans::Pop] Closing the character selection!");
+                    Trans::Pop
+                } else {
+                    Trans::None
+                }
+            }
+            StateEvent::Ui(UiEvent {
+                event_type: UiEventType::Click,
+                target,
+            }) => match self.buttons.iter().find(|(button, _)| *button == target) {
+                Some((_, character)) => {
+                    log::info!("Chose {:?}", character);
+                    let body = LobbyMessage::ChooseCharacter {
+                        character: character.name().map(str::to_string),
+                    };
+                    data.world
+                        .write_resource::<EventChannel<PlayerRequest>>()
+                        .single_write(PlayerRequest::Lobby(body));
+                    Trans::Pop
+                }
+                None => Trans::None,
+            },
+            _ => Trans::None,
+        }
+    }
+
+    fn update(&mut self, data: &mut StateData<'_, GameData>) -> SimpleTrans {
+        // this cannot happen in 'on_start', as the entity might not be fully
+        // initialized/registered/created yet.
+        if self.buttons.is_empty() {
+            data.world.exec(|finder: UiFinder<'_>| {
+                self.buttons = CHOICES
+                    .iter()
+                    .filter_map(|(character, id)| finder.find(id).map(|e| (e, *character)))
+                    .collect();
+            });
+        }
+        Trans::None
+    }
 }
@@ -6,8 +6,8 @@ use amethyst::{
     },
     network::simulation::{
         tcp::{
-            TcpConnectionListenerSystem, TcpNetworkRecvSystem, TcpNetworkResource,
-            TcpNetworkSendSystem, TcpStreamManagementSystem,
+            TcpNetworkRecvSystem, TcpNetworkResource, TcpNetworkSendSystem,
+            TcpStreamManagementSystem,
         },
         udp::{UdpNetworkRecvSystem, UdpNetworkSendSystem, UdpSocketResource},
         NetworkSimulationEvent, NetworkSimulationTime, NetworkSimulationTimeSystem,
@@ -22,91 +22,139 @@ use amethyst::{
 use log::{error, info, warn};
 use shared::{
     clientinfo::ClientInfo,
-    msg::{MessageLayer, MessageType, TransMessage},
+    codec::CodecKind,
+    handshake::{Capability, Hello, SessionToken},
+    heartbeat::Liveness,
+    msg::{
+        ConnectionMessage, GameAction, GameMessage, LobbyMessage, MessageError, Resync,
+        SystemMessage, TransMessage,
+    },
+    peer::Peer,
+    reliable::Delivery,
+    rules::CardDatabase,
+    transport::Transport,
+};
+use std::{
+    collections::HashMap,
+    net::{SocketAddr, UdpSocket},
+    time::{Duration, Instant},
 };
-use std::net::{SocketAddr, TcpListener, UdpSocket};
 
-use crate::{components::Player, entities::player::load_player, resources::SoundType};
+use crate::{
+    components::Player,
+    entities::player::{load_player, load_seats, move_player, remove_player, seat_position},
+    resources::{ConnectionStatus, Countdown, SoundType, Table},
+};
 
 use super::play_sfx::SoundEvent;
 
 const SERVER_ADDRESS: &str = "127.0.0.1:6666";
+/// How long to wait for the server to answer the handshake
+const HANDSHAKE_TIMEOUT_SECS: f64 = 5.0;
+
+/// Elsewhere in the application, you can broadcast `PlayerRequest`s. The `MessageSystem` sends
+/// them to the server once it accepted the handshake.
+#[derive(Debug, Clone)]
+pub enum PlayerRequest {
+    Lobby(LobbyMessage),
+    Game(GameAction),
+}
 
 #[derive(Debug, Default)]
 pub struct MessageBundle {
     pub server_info: ServerInfoResource,
     pub client_info: ClientInfo,
+    /// The udp socket to talk to the server, `None` to use a tcp connection instead
     pub socket: Option<UdpSocket>,
-    pub listener: Option<TcpListener>,
+    /// Only offer human-readable JSON to the server
+    pub debug_wire: bool,
 }
 
 impl MessageBundle {
     pub fn new(
         server_info: ServerInfoResource,
         client_info: ClientInfo,
-        socket: UdpSocket,
-        listener: TcpListener,
+        socket: Option<UdpSocket>,
+        debug_wire: bool,
     ) -> Self {
         Self {
             server_info,
             client_info,
-            socket: Some(socket),
-            listener: Some(listener),
+            socket,
+            debug_wire,
+        }
+    }
+
+    fn transport(&self) -> Transport {
+        if self.socket.is_some() {
+            Transport::Udp
+        } else {
+            Transport::Tcp
         }
     }
 }
 
 impl<'a, 'b> SystemBundle<'a, 'b> for MessageBundle {
     fn build(self, world: &mut World, builder: &mut DispatcherBuilder<'a, 'b>) -> Result<()> {
+        let transport = self.transport();
         builder.add(NetworkSimulationTimeSystem, "simulation_time", &[]);
 
-        builder.add(
-            TcpConnectionListenerSystem,
-            "connection_listener",
-            &["simulation_time"],
-        );
-
-        builder.add(
-            TcpStreamManagementSystem,
-            "stream_management",
-            &["simulation_time"],
-        );
-
-        builder.add(
-            TcpNetworkSendSystem,
-            "tcp_send",
-            &["stream_management", "connection_listener"],
-        );
+        match transport {
+            Transport::Tcp => {
+                // The stream to the server is opened with the first message sent to it
+                builder.add(
+                    TcpStreamManagementSystem,
+                    "stream_management",
+                    &["simulation_time"],
+                );
 
-        builder.add(
-            TcpNetworkRecvSystem,
-            "tcp_recv",
-            &["stream_management", "connection_listener"],
-        );
+                builder.add(TcpNetworkSendSystem, "tcp_send", &["stream_management"]);
 
-        world.insert(TcpNetworkResource::new(self.listener, 2048));
+                builder.add(TcpNetworkRecvSystem, "tcp_recv", &["stream_management"]);
 
-        builder.add(
-            UdpNetworkRecvSystem::with_buffer_capacity(2048),
-            "udp_recv",
-            &["simulation_time"],
-        );
-        builder.add(UdpNetworkSendSystem, "udp_send", &["simulation_time"]);
+                // The client only connects to the server, it does not accept connections
+                world.insert(TcpNetworkResource::new(None, 2048));
+            }
+            Transport::Udp => {
+                builder.add(
+                    UdpNetworkRecvSystem::with_buffer_capacity(2048),
+                    "udp_recv",
+                    &["simulation_time"],
+                );
+                builder.add(UdpNetworkSendSystem, "udp_send", &["simulation_time"]);
 
-        world.insert(UdpSocketResource::new(self.socket));
+                world.insert(UdpSocketResource::new(self.socket));
+            }
+        }
 
         world.insert(ServerInfoResource::new(self.server_info.addr));
         world.insert(ClientInfo::new(
             self.client_info.name,
             self.client_info.port,
         ));
-        builder.add(MessageSystemDesc.build(world), "message_system", &[]);
+        builder.add(
+            MessageSystemDesc::new(self.debug_wire, transport).build(world),
+            "message_system",
+            &[],
+        );
         Ok(())
     }
 }
 
-#[derive(Default, Debug)]
-pub struct MessageSystemDesc;
+#[derive(Debug)]
+pub struct MessageSystemDesc {
+    debug_wire: bool,
+    transport: Transport,
+}
+
+impl MessageSystemDesc {
+    pub fn new(debug_wire: bool, transport: Transport) -> Self {
+        Self {
+            debug_wire,
+            transport,
+        }
+    }
+}
 
 impl<'a, 'b> SystemDesc<'a, 'b, MessageSystem> for MessageSystemDesc {
     fn build(self, world: &mut World) -> MessageSystem {
@@ -120,10 +168,21 @@ impl<'a, 'b> SystemDesc<'a, 'b, MessageSystem> for MessageSystemDesc {
             .register_reader();
 
         let ui_reader = world.fetch_mut::<EventChannel<UiEvent>>().register_reader();
+        let request_reader = world
+            .fetch_mut::<EventChannel<PlayerRequest>>()
+            .register_reader();
 
         let client = world.fetch_mut::<ClientInfo>().clone();
         let server = world.fetch::<ServerInfoResource>().get_addr();
-        MessageSystem::new(network_reader, ui_reader, client, server)
+        MessageSystem::new(
+            network_reader,
+            ui_reader,
+            request_reader,
+            client,
+            server,
+            self.debug_wire,
+            self.transport,
+        )
     }
 }
 
@@ -131,32 +190,199 @@ impl<'a, 'b> SystemDesc<'a, 'b, MessageSystem> for MessageSystemDesc {
 struct MessageSystem {
     network_reader: ReaderId<NetworkSimulationEvent>,
     ui_reader: ReaderId<UiEvent>,
+    request_reader: ReaderId<PlayerRequest>,
     chat_output: Option<Entity>,
     client_info: ClientInfo,
     server_addr: SocketAddr,
     players: Vec<ClientInfo>,
+    // The number of seats at the table, once the server told where everybody sits
+    seat_count: Option<usize>,
+    // The seat every drawn player sits on
+    seated: HashMap<String, usize>,
+    // When the pending handshake was started, used for the timeout
+    handshake_started: Option<f64>,
+    // Reliable delivery to the server, speaks JSON until the server accepted the handshake
+    peer: Peer,
+    // When the server was last heard of, known once it accepted the handshake
+    liveness: Option<Liveness>,
+    // The session to resume after the connection was lost
+    session: Option<SessionToken>,
+    debug_wire: bool,
+    transport: Transport,
 }
 
 impl MessageSystem {
     pub fn new(
         network_reader: ReaderId<NetworkSimulationEvent>,
         ui_reader: ReaderId<UiEvent>,
+        request_reader: ReaderId<PlayerRequest>,
         client_info: ClientInfo,
         server_addr: SocketAddr,
+        debug_wire: bool,
+        transport: Transport,
     ) -> Self {
         Self {
             network_reader,
             ui_reader,
+            request_reader,
             chat_output: None,
             client_info,
             server_addr,
             players: vec![],
+            seat_count: None,
+            seated: HashMap::default(),
+            handshake_started: None,
+            peer: Peer::new(CodecKind::Json, transport),
+            liveness: None,
+            session: None,
+            debug_wire,
+            transport,
         }
     }
 
     fn find_ui_elements(&mut self, finder: &UiFinder) {
         self.chat_output = finder.find("lobby_multiline");
     }
+
+    /// Start the handshake once a state asked to connect or the connection was lost, and give
+    /// up if the server does not answer it in time.
+    fn update_handshake(&mut self, now: f64, status: &mut ConnectionStatus, instant: Instant) {
+        let reconnecting = match status {
+            ConnectionStatus::Connecting => false,
+            ConnectionStatus::Reconnecting => true,
+            _ => {
+                self.handshake_started = None;
+                return;
+            }
+        };
+        match self.handshake_started {
+            None => {
+                self.handshake_started = Some(now);
+                // Start a new connection, the server resets its side when it sees it
+                self.peer = Peer::new(CodecKind::Json, self.transport);
+                self.liveness = None;
+                if !reconnecting {
+                    self.session = None;
+                }
+                let codecs = if self.debug_wire {
+                    vec![CodecKind::Json]
+                } else {
+                    CodecKind::ALL.to_vec()
+                };
+                let mut hello = Hello::new(vec![Capability::Chat, Capability::Game], codecs);
+                hello.resume = self.session;
+                let msg = TransMessage::connection(
+                    self.client_info.clone(),
+                    ConnectionMessage::Hello(hello),
+                );
+                self.peer.send(msg, Delivery::Reliable, instant);
+            }
+            Some(started) if now - started > HANDSHAKE_TIMEOUT_SECS => {
+                *status = if reconnecting {
+                    ConnectionStatus::Lost
+                } else {
+                    ConnectionStatus::Failed("The server did not answer".to_string())
+                };
+            }
+            Some(_) => {}
+        }
+    }
+
+    /// Keep the server informed that the client is still there, and notice if the server is
+    /// not.
+    fn update_liveness(&mut self, status: &mut ConnectionStatus, now: Instant) {
+        let liveness = match (&*status, self.liveness.as_mut()) {
+            (ConnectionStatus::Connected(_), Some(liveness)) => liveness,
+            _ => return,
+        };
+        if liveness.timed_out(now) {
+            warn!("The server stopped answering");
+            *status = match self.session {
+                Some(_) => ConnectionStatus::Reconnecting,
+                None => ConnectionStatus::Lost,
+            };
+            return;
+        }
+        if liveness.heartbeat_due(now) && !self.peer.has_outgoing() {
+            let msg =
+                TransMessage::connection(self.client_info.clone(), ConnectionMessage::Heartbeat);
+            self.peer.send(msg, Delivery::Lossy, now);
+        }
+    }
+
+    /// Start over with the state of the server after the seat was taken again.
+    fn resync(&mut self, resync: Resync, lazy: &LazyUpdate, chat_output: Option<&mut UiText>) {
+        self.players = resync.players;
+        self.seat_players(resync.seats, lazy);
+        if let Some(output) = chat_output {
+            output.text = resync
+                .chat
+                .iter()
+                .map(|m| format!("[{}]:{} \n", m.from, m.body.text))
+                .collect();
+        }
+    }
+
+    /// Draw every player on the seat the server gave out, and take away those who left the
+    /// table. Every client sees the same order, the first seat at the bottom.
+    fn seat_players(&mut self, seats: Vec<Option<String>>, lazy: &LazyUpdate) {
+        let count = seats.len();
+        if self.seat_count != Some(count) {
+            self.seat_count = Some(count);
+            lazy.exec_mut(move |world| load_seats(world, count));
+        }
+        let gone: Vec<String> = self
+            .seated
+            .keys()
+            .filter(|name| !seats.iter().flatten().any(|s| s == *name))
+            .cloned()
+            .collect();
+        for name in gone {
+            self.seated.remove(&name);
+            lazy.exec_mut(move |world| remove_player(world, &name));
+        }
+        for (seat, name) in seats.into_iter().enumerate() {
+            let name = match name {
+                Some(name) => name,
+                None => continue,
+            };
+            let position = seat_position(seat, count);
+            match self.seated.insert(name.clone(), seat) {
+                Some(old) if old == seat => {}
+                Some(_) => lazy.exec_mut(move |world| move_player(world, &name, position)),
+                None => lazy.exec_mut(move |world| load_player(world, name, position)),
+            }
+        }
+    }
+
+    /// Retransmit overdue messages and hand everything queued over to the network. The
+    /// connection is started over if the server does not acknowledge a message, nothing
+    /// sent after it would be handled.
+    fn flush(&mut self, net: &mut TransportResource, status: &mut ConnectionStatus, now: Instant) {
+        match self.peer.update(now) {
+            Ok(()) => {}
+            Err(MessageError::Unacknowledged) => {
+                warn!("The server does not acknowledge messages");
+                *status = match (&*status, self.session) {
+                    (ConnectionStatus::Connected(_), Some(_)) => ConnectionStatus::Reconnecting,
+                    (ConnectionStatus::Connected(_), None)
+                    | (ConnectionStatus::Reconnecting, _) => ConnectionStatus::Lost,
+                    _ => ConnectionStatus::Failed("The server does not answer".to_string()),
+                };
+            }
+            Err(e) => warn!("Lost messages from or to the server: {:?}", e),
+        }
+        let datagrams = self.peer.drain_outgoing();
+        if !datagrams.is_empty() {
+            // Anything sent shows the server that the client is still there
+            if let Some(liveness) = self.liveness.as_mut() {
+                liveness.sent(now);
+            }
+        }
+        for datagram in datagrams {
+            net.send(self.server_addr, &datagram);
+        }
+    }
 }
 
 impl<'a> System<'a> for MessageSystem {
@@ -170,6 +396,10 @@ impl<'a> System<'a> for MessageSystem {
         WriteStorage<'a, UiText>,
         Write<'a, EventChannel<SoundEvent>>,
         Read<'a, LazyUpdate>,
+        Write<'a, ConnectionStatus>,
+        Read<'a, EventChannel<PlayerRequest>>,
+        Write<'a, Table>,
+        Read<'a, CardDatabase>,
     );
 
     fn run(
@@ -184,8 +414,15 @@ impl<'a> System<'a> for MessageSystem {
             mut ui_text,
             mut sound_channel,
             lazy,
+            mut status,
+            requests,
+            mut table,
+            cards,
         ): Self::SystemData,
     ) {
+        let now = Instant::now();
+        self.update_handshake(time.absolute_time_seconds(), &mut status, now);
+
         ui_event
             .read(&mut self.ui_reader)
             .filter(|event| event.event_type == UiEventType::ValueCommit)
@@ -201,58 +438,130 @@ impl<'a> System<'a> for MessageSystem {
                         &input.text,
                     );
 
-                    let trans_message = TransMessage::new(
-                        MessageLayer::Chat,
-                        self.client_info.clone(),
-                        shared::msg::MessageType::Chat,
-                        input.text.clone(),
-                    );
+                    let trans_message =
+                        TransMessage::chat(self.client_info.clone(), input.text.clone());
 
-                    net.send(
-                        self.server_addr,
-                        trans_message.serialize().unwrap().as_bytes(),
-                    );
+                    self.peer.send(trans_message, Delivery::Reliable, now);
                     // Reset input text
                     input.text = String::from("");
                 }
             });
 
+        for request in requests.read(&mut self.request_reader) {
+            if let ConnectionStatus::Connected(_) = *status {
+                let msg = match request.clone() {
+                    PlayerRequest::Lobby(body) => {
+                        TransMessage::lobby(self.client_info.clone(), body)
+                    }
+                    PlayerRequest::Game(action) => {
+                        TransMessage::game(self.client_info.clone(), GameMessage::Action(action))
+                    }
+                };
+                self.peer.send(msg, Delivery::Reliable, now);
+            } else {
+                warn!("Drop {:?}, not connected to the server", request);
+            }
+        }
+
         for event in event.read(&mut self.network_reader) {
             match event {
                 NetworkSimulationEvent::Message(addr, payload) => {
                     info!("Client Received from {}: {:?}", addr, payload);
-                    if let Ok(resp) = serde_json::from_slice::<TransMessage>(payload) {
+                    let mut messages = vec![];
+                    if let Err(e) = self.peer.receive(payload, now, &mut messages) {
+                        warn!(
+                            "Received messages that cannot be processed: {:?}, {:?}",
+                            e,
+                            String::from_utf8_lossy(payload)
+                        );
+                        if let ConnectionStatus::Connecting = *status {
+                            *status = ConnectionStatus::Failed(
+                                "The server speaks an incompatible protocol".to_string(),
+                            );
+                        } else if self.peer.is_broken() {
+                            warn!("The stream from the server is corrupt");
+                            *status = ConnectionStatus::Lost;
+                        }
+                        if messages.is_empty() {
+                            continue;
+                        }
+                    }
+                    if let Some(liveness) = self.liveness.as_mut() {
+                        liveness.seen(now);
+                    }
+                    for resp in messages {
                         info!("msg is {:?}", resp);
                         self.find_ui_elements(&ui_finder);
                         match resp {
-                            TransMessage::Connection(m) => {
-                                if m.msg_type == MessageType::EnterLobby {
+                            TransMessage::Connection(m) => match m.body {
+                                ConnectionMessage::Accepted(welcome) => {
+                                    info!(
+                                        "Received: [Accepted] by server [{}]",
+                                        welcome.server_name
+                                    );
+                                    self.peer.set_codec(welcome.codec);
+                                    self.liveness = Some(Liveness::new(welcome.heartbeat, now));
+                                    self.session = Some(welcome.session);
+                                    let resumed = welcome.resumed;
+                                    *status = ConnectionStatus::Connected(welcome);
+                                    // A resumed seat is in the lobby already
+                                    if !resumed {
+                                        let msg = TransMessage::connection(
+                                            self.client_info.clone(),
+                                            ConnectionMessage::EnterLobby,
+                                        );
+                                        self.peer.send(msg, Delivery::Reliable, now);
+                                    }
+                                }
+                                ConnectionMessage::Rejected(reason) => {
+                                    info!("Received: [Rejected] {:?}", reason);
+                                    *status = ConnectionStatus::Failed(reason.to_string());
+                                }
+                                ConnectionMessage::PlayerEntered(client) => {
                                     info!("Received: [PlayerEnterLobby]");
-                                    let num = self.players.len();
-                                    let client = m.from;
-                                    if self.players.contains(&client) {
-                                        continue;
+                                    // The player is drawn once the server gave out the seat
+                                    if !self.players.contains(&client) {
+                                        self.players.push(client);
                                     }
-                                    self.players.push(client.clone());
-                                    log::info!("[Chat] Prepare loading player");
-                                    lazy.exec_mut(move |world| {
-                                        load_player(world, client.name, num);
-                                    });
-                                } else if m.msg_type == MessageType::Exit {
+                                }
+                                ConnectionMessage::PlayerExited(client)
+                                | ConnectionMessage::PlayerTimedOut(client) => {
                                     info!("Received: [PlayerExitGame]");
-                                    let client = m.from;
-                                    if self.players.contains(&client) {
-                                        let x = self
-                                            .players
-                                            .binary_search(&client)
-                                            .unwrap_or_else(|x| x);
-                                        self.players.remove(x - 1);
-                                        // TODO: Remove player entity
+                                    self.players.retain(|p| *p != client);
+                                }
+                                ConnectionMessage::Heartbeat => {}
+                                ConnectionMessage::Resync(resync) => {
+                                    info!("Received: [Resync]");
+                                    let chat_output =
+                                        self.chat_output.and_then(|e| ui_text.get_mut(e));
+                                    self.resync(resync, &lazy, chat_output);
+                                }
+                                body => warn!("Unexpected connection message {:?}", body),
+                            },
+                            TransMessage::System(m) => match m.body {
+                                SystemMessage::Notice(text) => {
+                                    info!("Received: [Notice] {}", text);
+                                    if let Some(output) =
+                                        self.chat_output.and_then(|e| ui_text.get_mut(e))
+                                    {
+                                        output.text =
+                                            format!("{}[{}]:{} \n", output.text, m.from, text);
                                     }
                                 }
-                            }
-                            TransMessage::System(_) => todo!(),
-                            TransMessage::Lobby(_) => todo!(),
+                            },
+                            TransMessage::Lobby(m) => match m.body {
+                                LobbyMessage::PlayerPrepared { player, prepared } => {
+                                    info!("Received: [PlayerPrepared] {} {}", player, prepared);
+                                }
+                                LobbyMessage::CharacterChosen { player, character } => {
+                                    info!("Received: [CharacterChosen] {} {:?}", player, character);
+                                }
+                                LobbyMessage::Seats { seats } => {
+                                    info!("Received: [Seats] {:?}", seats);
+                                    self.seat_players(seats, &lazy);
+                                }
+                                body => warn!("Unexpected lobby message {:?}", body),
+                            },
                             TransMessage::Chat(m) => {
                                 info!("Received: [ForwardChatMessage]");
                                 info!("[Chat] Update chatbox output");
@@ -261,20 +570,51 @@ impl<'a> System<'a> for MessageSystem {
                                     if let Some(output) = ui_text.get_mut(chat_output) {
                                         let total_msg = output.text.clone();
                                         let new_total_msg =
-                                            format!("{}[{}]:{} \n", total_msg, m.from, m.msg);
+                                            format!("{}[{}]:{} \n", total_msg, m.from, m.body.text);
                                         info!("[Chat] Update chatbox content: {}", new_total_msg);
                                         output.text = new_total_msg;
                                     }
                                 }
                             }
-                            TransMessage::Game(_) => todo!(),
+                            TransMessage::Game(m) => match m.body {
+                                GameMessage::Event { seq, event } => {
+                                    info!("Received: [GameEvent] {} {:?}", seq, event);
+                                    table.receive(seq, &event, &cards);
+                                }
+                                GameMessage::Replay { seq, events } => {
+                                    info!("Received: [Replay] {} events", events.len());
+                                    *table = Table::default();
+                                    for event in &events {
+                                        table.apply(event, &cards);
+                                    }
+                                    // Copies of the replayed events are dropped
+                                    table.seq = seq;
+                                }
+                                GameMessage::Rejected(reason) => {
+                                    warn!("The server rejected the last action: {}", reason);
+                                    table.rejected = Some(reason);
+                                }
+                                GameMessage::Prompt { prompt, timeout } => {
+                                    info!("Asked to respond to {:?}", prompt);
+                                    let timeout = Duration::from_secs(timeout.into());
+                                    table.prompt = Some(prompt);
+                                    table.respond_by = Some(now + timeout);
+                                }
+                                GameMessage::Timer {
+                                    player,
+                                    seconds,
+                                    bank,
+                                } => {
+                                    table.countdown = Some(Countdown {
+                                        player,
+                                        seconds,
+                                        bank,
+                                    });
+                                }
+                                body => warn!("Unexpected game message {:?}", body),
+                            },
                         }
-                    } else {
-                        warn!(
-                            "Received messages that cannot be processed. {:?}",
-                            String::from_utf8(payload.clone().to_vec()).unwrap()
-                        );
-                    };
+                    }
                 }
                 NetworkSimulationEvent::Connect(addr) => {
                     info!("New client connection: {}", addr);
@@ -289,6 +629,8 @@ impl<'a> System<'a> for MessageSystem {
                 _ => {}
             }
         }
+        self.update_liveness(&mut status, now);
+        self.flush(&mut net, &mut status, now);
     }
 
     fn setup(&mut self, world: &mut World) {
@@ -0,0 +1,31 @@
+use log::{LevelFilter, Log, Metadata, Record, SetLoggerError};
+
+/// Prints the log to stderr, in the same format the amethyst logger used.
+struct Logger {
+    level: LevelFilter,
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level
+    }
+
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            eprintln!(
+                "[{}][{}] {}",
+                record.level(),
+                record.target(),
+                record.args()
+            );
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+pub fn start_logger(level: LevelFilter) -> Result<(), SetLoggerError> {
+    log::set_boxed_logger(Box::new(Logger { level }))?;
+    log::set_max_level(level);
+    Ok(())
+}
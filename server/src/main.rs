@@ -1,19 +1,27 @@
-use amethyst::{
-    core::frame_limiter::FrameRateLimitStrategy, prelude::*, utils::application_root_dir, Result,
+use log::LevelFilter;
+use network::Network;
+use service::{Service, ServiceConfig};
+use shared::{
+    rules::{CardDatabase, GameMode},
+    utilities::{heartbeat::HeartbeatConfig, transport::Transport},
 };
+use std::error::Error;
 use std::net::{SocketAddr, TcpListener, UdpSocket};
-use std::time::Duration;
+use std::path::PathBuf;
+use std::thread;
+use std::time::{Duration, Instant};
 use structopt::StructOpt;
-use systems::service::ServiceBundle;
 
-mod systems;
+mod logger;
+mod network;
+mod service;
 
-/// Default empty state
-pub struct GameState;
+/// How often per second the server handles what arrived and answers it.
+const TICK_RATE: u32 = 60;
+/// Size of the buffer a single datagram or stream read goes into.
+const RECV_BUFFER_SIZE_BYTES: usize = 2048;
 
-impl SimpleState for GameState {}
-
-fn main() -> Result<()> {
+fn main() -> Result<(), Box<dyn Error>> {
     let server = Server::init();
     server.run()
 }
@@ -25,34 +33,121 @@ pub struct Server {
     #[structopt(short, default_value = "6666")]
     pub port: u16,
 
+    /// The name of the server, which is sent to the clients during the handshake.
     #[structopt(long, default_value = "server")]
     pub name: String,
+
+    /// Keep human-readable JSON on the wire instead of the compact binary format.
+    #[structopt(long)]
+    pub debug_wire: bool,
+
+    /// Carry all traffic over udp datagrams or a tcp stream, the clients must use the same.
+    #[structopt(long, default_value = "udp", possible_values = &["udp", "tcp"])]
+    pub transport: Transport,
+
+    /// Seconds between two heartbeats sent to the clients.
+    #[structopt(long, default_value = "1")]
+    pub heartbeat_interval: u64,
+
+    /// Seconds without any message after which a client is removed.
+    #[structopt(long, default_value = "10")]
+    pub idle_timeout: u64,
+
+    /// Seconds a player who lost the connection can take the seat again.
+    #[structopt(long, default_value = "30")]
+    pub reconnect_grace: u64,
+
+    /// The card database, the clients must load the same file.
+    #[structopt(
+        long,
+        default_value = "client/assets/config/cards.ron",
+        parse(from_os_str)
+    )]
+    pub cards: PathBuf,
+
+    /// Shuffle every game with this seed, to play a game from the log again.
+    #[structopt(long)]
+    pub seed: Option<u64>,
+
+    /// How games are won: hidden roles, a free-for-all or a duel of two players.
+    #[structopt(long, default_value = "roles", possible_values = &["roles", "ffa", "duel"])]
+    pub mode: GameMode,
+
+    /// Seats at the table, at most 8. The game mode decides without it, a duel always has 2.
+    #[structopt(long)]
+    pub seats: Option<usize>,
+
+    /// Seconds a player has to respond to another player's card before passing.
+    #[structopt(long, default_value = "15")]
+    pub response_timeout: u32,
+
+    /// Seconds a player has for the play phase before it ends on its own.
+    #[structopt(long, default_value = "60")]
+    pub turn_timeout: u32,
+
+    /// Seconds each player may go over the time limits during a game, none with 0.
+    #[structopt(long, default_value = "0")]
+    pub time_bank: u32,
+
+    /// Only log messages of this level or above: error, warn, info, debug or trace.
+    #[structopt(long, default_value = "info")]
+    pub log_level: LevelFilter,
 }
 
 impl Server {
     pub fn init() -> Self {
         Server::from_args()
     }
-    pub fn run(self) -> Result<()> {
+    pub fn run(self) -> Result<(), Box<dyn Error>> {
         let listener_addrs = SocketAddr::from(([0, 0, 0, 0], self.port));
 
-        amethyst::start_logger(Default::default());
-        let listener = TcpListener::bind(listener_addrs)?;
-        listener.set_nonblocking(true)?;
-
-        let socket = UdpSocket::bind(listener_addrs)?;
-        socket.set_nonblocking(true)?;
-        let assets_dir = application_root_dir()?.join("assets");
-        let game_data =
-            GameDataBuilder::default().with_bundle(ServiceBundle::new(listener, socket, 2048))?;
-
-        let mut game = Application::build(assets_dir, GameState)?
-            .with_frame_limit(
-                FrameRateLimitStrategy::SleepAndYield(Duration::from_millis(2)),
-                60,
-            )
-            .build(game_data)?;
-        game.run();
-        Ok(())
+        logger::start_logger(self.log_level)?;
+        let cards = CardDatabase::load(&self.cards)
+            .map_err(|e| format!("Failed to load the cards from {:?}: {}", self.cards, e))?;
+        log::info!(
+            "Loaded {} kinds of cards, {} in a deck",
+            cards.definitions().len(),
+            cards.deck_size()
+        );
+        let config = ServiceConfig {
+            name: self.name,
+            debug_wire: self.debug_wire,
+            heartbeat: HeartbeatConfig {
+                interval: Duration::from_secs(self.heartbeat_interval),
+                timeout: Duration::from_secs(self.idle_timeout),
+            },
+            reconnect_grace: Duration::from_secs(self.reconnect_grace),
+            cards,
+            seed: self.seed,
+            response_timeout: self.response_timeout,
+            turn_timeout: self.turn_timeout,
+            time_bank: self.time_bank,
+            mode: self.mode,
+            seats: self.seats,
+        };
+        let mut network = match self.transport {
+            Transport::Tcp => {
+                Network::tcp(TcpListener::bind(listener_addrs)?, RECV_BUFFER_SIZE_BYTES)?
+            }
+            Transport::Udp => {
+                Network::udp(UdpSocket::bind(listener_addrs)?, RECV_BUFFER_SIZE_BYTES)?
+            }
+        };
+        log::info!(
+            "Listening on {} over {}",
+            listener_addrs,
+            network.transport()
+        );
+        let mut service = Service::new(config, network.transport());
+
+        let tick = Duration::from_secs(1) / TICK_RATE;
+        loop {
+            let started = Instant::now();
+            service.update(&mut network, started);
+            let elapsed = started.elapsed();
+            if elapsed < tick {
+                thread::sleep(tick - elapsed);
+            }
+        }
     }
 }
@@ -0,0 +1,262 @@
+use std::{
+    collections::HashMap,
+    io::{self, ErrorKind, Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream, UdpSocket},
+};
+
+use log::{debug, error, info, warn};
+use shared::utilities::transport::Transport;
+
+/// What happened on the sockets since the last poll.
+#[derive(Debug)]
+pub enum NetworkEvent {
+    /// Bytes from a client: a whole datagram, or whatever could be read from a stream.
+    Message(SocketAddr, Vec<u8>),
+    /// A client opened a tcp stream.
+    Connect(SocketAddr),
+    /// The tcp stream of a client was closed.
+    Disconnect(SocketAddr),
+    RecvError(io::Error),
+}
+
+/// The non-blocking sockets the server talks to its clients with.
+pub enum Network {
+    Udp(UdpNetwork),
+    Tcp(TcpNetwork),
+}
+
+impl Network {
+    pub fn udp(socket: UdpSocket, recv_buffer_size_bytes: usize) -> io::Result<Self> {
+        socket.set_nonblocking(true)?;
+        Ok(Network::Udp(UdpNetwork {
+            socket,
+            buffer: vec![0; recv_buffer_size_bytes],
+        }))
+    }
+
+    pub fn tcp(listener: TcpListener, recv_buffer_size_bytes: usize) -> io::Result<Self> {
+        listener.set_nonblocking(true)?;
+        Ok(Network::Tcp(TcpNetwork {
+            listener,
+            streams: HashMap::default(),
+            closed: Vec::new(),
+            buffer: vec![0; recv_buffer_size_bytes],
+        }))
+    }
+
+    pub fn transport(&self) -> Transport {
+        match self {
+            Network::Udp(_) => Transport::Udp,
+            Network::Tcp(_) => Transport::Tcp,
+        }
+    }
+
+    /// Everything that arrived since the last call, without waiting for anything.
+    pub fn poll(&mut self) -> Vec<NetworkEvent> {
+        match self {
+            Network::Udp(udp) => udp.poll(),
+            Network::Tcp(tcp) => tcp.poll(),
+        }
+    }
+
+    pub fn send(&mut self, addr: SocketAddr, bytes: &[u8]) {
+        match self {
+            Network::Udp(udp) => udp.send(addr, bytes),
+            Network::Tcp(tcp) => tcp.send(addr, bytes),
+        }
+    }
+
+    /// Close the stream of a client, which is reported as `Disconnect` with the next poll.
+    /// Datagrams have no connection to close.
+    pub fn close(&mut self, addr: SocketAddr) {
+        if let Network::Tcp(tcp) = self {
+            tcp.close(addr);
+        }
+    }
+}
+
+pub struct UdpNetwork {
+    socket: UdpSocket,
+    buffer: Vec<u8>,
+}
+
+impl UdpNetwork {
+    fn poll(&mut self) -> Vec<NetworkEvent> {
+        let mut events = vec![];
+        loop {
+            match self.socket.recv_from(&mut self.buffer) {
+                Ok((len, addr)) => {
+                    events.push(NetworkEvent::Message(addr, self.buffer[..len].to_vec()));
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                // Windows reports datagrams that could not be delivered earlier, ignore them
+                Err(e) if e.kind() == ErrorKind::ConnectionReset => continue,
+                Err(e) => {
+                    events.push(NetworkEvent::RecvError(e));
+                    break;
+                }
+            }
+        }
+        events
+    }
+
+    fn send(&mut self, addr: SocketAddr, bytes: &[u8]) {
+        if let Err(e) = self.socket.send_to(bytes, addr) {
+            warn!("Send to the client[{}] failed: {}", addr, e);
+        }
+    }
+}
+
+struct Stream {
+    stream: TcpStream,
+    // Bytes the socket did not take yet
+    outgoing: Vec<u8>,
+}
+
+pub struct TcpNetwork {
+    listener: TcpListener,
+    streams: HashMap<SocketAddr, Stream>,
+    // Streams that failed while sending or were closed, reported with the next poll
+    closed: Vec<SocketAddr>,
+    buffer: Vec<u8>,
+}
+
+impl TcpNetwork {
+    fn poll(&mut self) -> Vec<NetworkEvent> {
+        let mut events: Vec<NetworkEvent> = self
+            .closed
+            .drain(..)
+            .map(NetworkEvent::Disconnect)
+            .collect();
+
+        loop {
+            match self.listener.accept() {
+                Ok((stream, addr)) => {
+                    if let Err(e) = stream.set_nonblocking(true) {
+                        error!("Failed to set up the stream of [{}]: {}", addr, e);
+                        continue;
+                    }
+                    let _ = stream.set_nodelay(true);
+                    self.streams.insert(
+                        addr,
+                        Stream {
+                            stream,
+                            outgoing: Vec::new(),
+                        },
+                    );
+                    events.push(NetworkEvent::Connect(addr));
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) => {
+                    events.push(NetworkEvent::RecvError(e));
+                    break;
+                }
+            }
+        }
+
+        let mut closed = vec![];
+        for (addr, stream) in self.streams.iter_mut() {
+            loop {
+                match stream.stream.read(&mut self.buffer) {
+                    Ok(0) => {
+                        closed.push(*addr);
+                        break;
+                    }
+                    Ok(len) => {
+                        events.push(NetworkEvent::Message(*addr, self.buffer[..len].to_vec()))
+                    }
+                    Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                    Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                    Err(e) => {
+                        debug!("Read from [{}] failed: {}", addr, e);
+                        closed.push(*addr);
+                        break;
+                    }
+                }
+            }
+            // Retry what the socket did not take last time
+            if !stream.outgoing.is_empty() && write_pending(stream).is_err() {
+                closed.push(*addr);
+            }
+        }
+        // A stream may fail both reading and writing, it is gone once
+        for addr in closed {
+            if self.streams.remove(&addr).is_some() {
+                events.push(NetworkEvent::Disconnect(addr));
+            }
+        }
+        events
+    }
+
+    fn close(&mut self, addr: SocketAddr) {
+        if self.streams.remove(&addr).is_some() {
+            self.closed.push(addr);
+        }
+    }
+
+    fn send(&mut self, addr: SocketAddr, bytes: &[u8]) {
+        let stream = match self.streams.get_mut(&addr) {
+            Some(stream) => stream,
+            None => {
+                warn!("No open stream to the client[{}]", addr);
+                return;
+            }
+        };
+        stream.outgoing.extend_from_slice(bytes);
+        if let Err(e) = write_pending(stream) {
+            info!("Send to the client[{}] failed: {}", addr, e);
+            self.close(addr);
+        }
+    }
+}
+
+/// Write as much of the pending bytes as the socket takes without blocking.
+fn write_pending(stream: &mut Stream) -> io::Result<()> {
+    while !stream.outgoing.is_empty() {
+        match stream.stream.write(&stream.outgoing) {
+            Ok(0) => return Err(ErrorKind::WriteZero.into()),
+            Ok(len) => {
+                stream.outgoing.drain(..len);
+            }
+            Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{thread, time::Duration};
+
+    /// Everything that arrives within a moment.
+    fn poll_for_a_moment(net: &mut Network) -> Vec<NetworkEvent> {
+        let mut events = vec![];
+        for _ in 0..20 {
+            events.extend(net.poll());
+            thread::sleep(Duration::from_millis(5));
+        }
+        events
+    }
+
+    #[test]
+    fn a_closed_stream_is_reported_once() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let server = listener.local_addr().unwrap();
+        let mut net = Network::tcp(listener, 64).unwrap();
+        let client = TcpStream::connect(server).unwrap();
+        let addr = client.local_addr().unwrap();
+        let events = poll_for_a_moment(&mut net);
+        assert!(matches!(events.as_slice(), [NetworkEvent::Connect(a)] if *a == addr));
+
+        // Closed by both sides, and closed again
+        drop(client);
+        net.close(addr);
+        net.close(addr);
+        net.send(addr, b"gone");
+        let events = poll_for_a_moment(&mut net);
+        assert!(matches!(events.as_slice(), [NetworkEvent::Disconnect(a)] if *a == addr));
+    }
+}
@@ -0,0 +1,1293 @@
+use std::{
+    collections::{hash_map::RandomState, HashMap, HashSet, VecDeque},
+    hash::{BuildHasher, Hasher},
+    net::SocketAddr,
+    time::{Duration, Instant},
+};
+
+use log::{debug, error, info, trace, warn};
+use shared::{
+    clientinfo::ClientInfo,
+    rules::{ActionError, CardDatabase, Engine, GameEvent, GameMode, Phase},
+    utilities::{
+        codec::CodecKind,
+        handshake::{
+            Capability, Hello, RejectReason, RuleSet, SessionToken, Welcome, MAX_SEATS,
+            REQUIRED_CAPABILITIES,
+        },
+        heartbeat::{HeartbeatConfig, Liveness},
+        msg::{
+            ChatMessage, ConnectionMessage, GameAction, GameMessage, LobbyMessage, Message,
+            MessageError, Resync, SystemMessage, TransMessage,
+        },
+        peer::Peer,
+        reliable::Delivery,
+        transport::Transport,
+    },
+};
+
+use crate::network::{Network, NetworkEvent};
+
+/// Settings of the service which are given on the command line.
+#[derive(Debug, Clone)]
+pub struct ServiceConfig {
+    /// Sent to the clients during the handshake
+    pub name: String,
+    /// Force human-readable JSON for all clients
+    pub debug_wire: bool,
+    pub heartbeat: HeartbeatConfig,
+    /// How long the seat of a player who lost the connection is kept for a reconnect
+    pub reconnect_grace: Duration,
+    /// The cards games are played with
+    pub cards: CardDatabase,
+    /// Shuffle every game with this seed instead of a random one, to play a game again
+    pub seed: Option<u64>,
+    /// Seconds a player has to respond before passing
+    pub response_timeout: u32,
+    /// Seconds a player has for the play phase before it ends
+    pub turn_timeout: u32,
+    /// Seconds each player may go over the time limits during a game
+    pub time_bank: u32,
+    /// How games are won, it chooses the preset of the rules
+    pub mode: GameMode,
+    /// Seats at the table instead of those of the preset
+    pub seats: Option<usize>,
+}
+
+/// Number of chat messages a reconnecting client gets to see again.
+const CHAT_BACKLOG: usize = 50;
+
+/// A client the server has received messages from.
+struct Remote {
+    peer: Peer,
+    liveness: Liveness,
+}
+
+/// Why a client is gone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Departure {
+    /// The tcp stream was closed.
+    Disconnected,
+    /// Nothing arrived from the client for too long.
+    TimedOut,
+    /// The client said goodbye, it will not come back.
+    Left,
+}
+
+/// The seat of a player whose connection was lost, waiting for the player to come back.
+struct Suspended {
+    player: ClientInfo,
+    since: Instant,
+    departure: Departure,
+}
+
+/// The time the player the game waits for has left to act.
+struct Clock {
+    player: String,
+    /// Whether it runs for the play phase, not for an answer to a prompt
+    turn: bool,
+    deadline: Instant,
+    /// When the player started to use the time bank, after the time was up
+    banked_since: Option<Instant>,
+    /// The seconds the players were told last
+    shown: Option<u32>,
+}
+
+/// Everything the server knows about its clients. It is driven by the main loop, which
+/// calls `update` once per tick.
+pub struct Service {
+    connection: Vec<SocketAddr>,
+    players: HashMap<SocketAddr, ClientInfo>,
+    // Clients that passed the handshake but did not enter the lobby yet, with their names
+    accepted: HashMap<SocketAddr, String>,
+    online_num: u32,
+    // The sender of messages that originate from the server itself
+    info: ClientInfo,
+    rules: RuleSet,
+    // One remote for every address messages arrived from, replies go back to that address
+    remotes: HashMap<SocketAddr, Remote>,
+    // Force human-readable JSON for all clients
+    debug_wire: bool,
+    transport: Transport,
+    heartbeat: HeartbeatConfig,
+    // The session of every accepted client
+    sessions: HashMap<SocketAddr, SessionToken>,
+    // Seats of players who lost the connection
+    suspended: HashMap<SessionToken, Suspended>,
+    reconnect_grace: Duration,
+    chat_log: VecDeque<Message<ChatMessage>>,
+    cards: CardDatabase,
+    seed: Option<u64>,
+    // Players in the lobby who are ready to start a game
+    prepared: HashSet<SocketAddr>,
+    // The characters players in the lobby chose for the next game
+    characters: HashMap<SocketAddr, String>,
+    // The running game, if any
+    game: Option<Engine>,
+    // Who the game waits for, and until when
+    clock: Option<Clock>,
+    // What is left of the play phase while the clock runs for a prompt
+    turn_left: Option<Duration>,
+    // What is left of the time bank of each player in the game
+    banks: HashMap<String, Duration>,
+    // The name of the player on every seat, as many as the rules allow
+    seats: Vec<Option<String>>,
+}
+
+impl Service {
+    pub fn new(config: ServiceConfig, transport: Transport) -> Self {
+        let mut rules = RuleSet {
+            response_timeout: config.response_timeout,
+            turn_timeout: config.turn_timeout,
+            time_bank: config.time_bank,
+            ..RuleSet::preset(config.mode)
+        };
+        match config.seats {
+            // The mode decides how many play, such as the two of a duel
+            Some(seats) if rules.min_players == rules.max_players => warn!(
+                "The {} rules are played on {} seats, ignore --seats {}",
+                rules.name, rules.max_players, seats
+            ),
+            Some(seats) => rules.max_players = seats.max(rules.min_players).min(MAX_SEATS),
+            None => {}
+        }
+        Self {
+            connection: Vec::new(),
+            players: HashMap::default(),
+            accepted: HashMap::default(),
+            online_num: 0,
+            info: ClientInfo::new(config.name, 0),
+            seats: vec![None; rules.max_players],
+            rules,
+            remotes: HashMap::default(),
+            debug_wire: config.debug_wire,
+            transport,
+            heartbeat: config.heartbeat,
+            sessions: HashMap::default(),
+            suspended: HashMap::default(),
+            reconnect_grace: config.reconnect_grace,
+            chat_log: VecDeque::with_capacity(CHAT_BACKLOG),
+            cards: config.cards,
+            seed: config.seed,
+            prepared: HashSet::default(),
+            characters: HashMap::default(),
+            game: None,
+            clock: None,
+            turn_left: None,
+            banks: HashMap::default(),
+        }
+    }
+
+    fn send(&mut self, addr: SocketAddr, msg: TransMessage, now: Instant) {
+        match self.remotes.get_mut(&addr) {
+            Some(remote) => remote.peer.send(msg, Delivery::Reliable, now),
+            None => warn!("Drop message to unknown client [{}]: {}", addr, msg),
+        }
+    }
+
+    /// Tell the client why a request was refused, the player is shown the text.
+    fn notice(&mut self, addr: SocketAddr, text: String, now: Instant) {
+        let msg = TransMessage::system(self.info.clone(), SystemMessage::Notice(text));
+        self.send(addr, msg, now);
+    }
+
+    /// Send a message to every player in the lobby.
+    fn broadcast(&mut self, msg: TransMessage, now: Instant) {
+        let addrs: Vec<SocketAddr> = self.players.keys().copied().collect();
+        for addr in addrs {
+            self.send(addr, msg.clone(), now);
+        }
+    }
+
+    /// Check the protocol version and capabilities of a new client and answer it.
+    fn handshake(
+        &mut self,
+        net: &mut Network,
+        addr: SocketAddr,
+        from: &ClientInfo,
+        hello: Hello,
+        now: Instant,
+    ) {
+        info!("Received: [Hello] {:?} from [{}]", hello, from.name);
+        let mut codec = CodecKind::Json;
+        let mut resumed = false;
+        let reply = match hello.verify() {
+            Ok(()) => {
+                resumed = match hello.resume {
+                    Some(token) => self.resume(addr, token, now),
+                    None => false,
+                };
+                if !resumed && self.seats_taken(addr) >= self.rules.max_players {
+                    warn!("Reject the client[{}]: the lobby is full", from.name);
+                    ConnectionMessage::Rejected(RejectReason::LobbyFull)
+                } else if !resumed && self.name_taken(addr, &from.name) {
+                    warn!("Reject the client[{}]: the name is taken", from.name);
+                    ConnectionMessage::Rejected(RejectReason::NameTaken(from.name.clone()))
+                } else {
+                    if !resumed {
+                        self.accepted.insert(addr, from.name.clone());
+                        self.sessions.insert(addr, random());
+                    }
+                    codec = hello.select_codec(self.debug_wire);
+                    let capabilities: Vec<Capability> = hello
+                        .capabilities
+                        .into_iter()
+                        .filter(|c| REQUIRED_CAPABILITIES.contains(c))
+                        .collect();
+                    ConnectionMessage::Accepted(Welcome {
+                        server_name: self.info.name.clone(),
+                        rules: self.rules.clone(),
+                        capabilities,
+                        codec,
+                        heartbeat: self.heartbeat,
+                        session: self.sessions[&addr],
+                        resumed,
+                    })
+                }
+            }
+            Err(reason) => {
+                warn!("Reject the client[{}]: {}", from.name, reason);
+                ConnectionMessage::Rejected(reason)
+            }
+        };
+        let msg = TransMessage::connection(self.info.clone(), reply);
+        self.send(addr, msg, now);
+        if let Some(remote) = self.remotes.get_mut(&addr) {
+            // Send the answer before switching, the handshake is always answered in JSON
+            send_datagrams(net, addr, remote, now);
+            remote.peer.set_codec(codec);
+        }
+        if resumed {
+            // The seat decides what the client may see, not the name the client claims
+            let name = self.players[&addr].name.clone();
+            info!("Client[{}] took its seat again", name);
+            let resync = Resync {
+                players: self
+                    .players
+                    .values()
+                    .chain(self.suspended.values().map(|s| &s.player))
+                    .cloned()
+                    .collect(),
+                chat: self.chat_log.iter().cloned().collect(),
+                seats: self.seats.clone(),
+            };
+            let msg =
+                TransMessage::connection(self.info.clone(), ConnectionMessage::Resync(resync));
+            self.send(addr, msg, now);
+            let viewer = self.viewer(&name);
+            let replay = match self.game.as_ref() {
+                Some(game) => GameMessage::Replay {
+                    seq: game.log().len().checked_sub(1).map(|last| last as u64),
+                    events: game.log().iter().filter_map(|e| e.view(viewer)).collect(),
+                },
+                None => GameMessage::Replay {
+                    seq: None,
+                    events: vec![],
+                },
+            };
+            let msg = TransMessage::game(self.info.clone(), replay);
+            self.send(addr, msg, now);
+        }
+    }
+
+    /// Give the seat of the session back to the client at `addr`. Fails if the session is
+    /// unknown or its grace period is over.
+    fn resume(&mut self, addr: SocketAddr, token: SessionToken, now: Instant) -> bool {
+        if self.sessions.get(&addr) == Some(&token) && self.players.contains_key(&addr) {
+            // The client restarted its connection before the server noticed anything
+            return true;
+        }
+        // The player may still be seated with its old address
+        let old = self
+            .sessions
+            .iter()
+            .find(|(a, t)| **t == token && **a != addr)
+            .map(|(a, _)| *a);
+        if let Some(old) = old {
+            self.drop_client(&old, Departure::Disconnected, now);
+        }
+        match self.suspended.remove(&token) {
+            Some(suspended) => {
+                self.players.insert(addr, suspended.player);
+                self.sessions.insert(addr, token);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Number of seats the clients other than the one at `addr` have or are about to take:
+    /// those of the players, of the players who lost the connection and of the clients that
+    /// passed the handshake but did not enter the lobby yet.
+    fn seats_taken(&self, addr: SocketAddr) -> usize {
+        let players = self.players.keys().filter(|a| **a != addr).count();
+        let accepted = self.accepted.keys().filter(|a| **a != addr).count();
+        players + accepted + self.suspended.len()
+    }
+
+    /// Whether a client other than the one at `addr` goes by the name. Seats, the game and
+    /// what each player may see are all told apart by name.
+    fn name_taken(&self, addr: SocketAddr, name: &str) -> bool {
+        self.players
+            .iter()
+            .any(|(a, p)| *a != addr && p.name == name)
+            || self.accepted.iter().any(|(a, n)| *a != addr && n == name)
+            || self.suspended.values().any(|s| s.player.name == name)
+    }
+
+    fn enter_lobby(&mut self, addr: SocketAddr, from: ClientInfo, now: Instant) {
+        info!("Received: [ConnectRequest]");
+        match self.accepted.remove(&addr) {
+            Some(name) if name == from.name => {}
+            Some(name) => {
+                warn!("Client[{}] entered the lobby as [{}]", name, from.name);
+                return;
+            }
+            None => {
+                warn!("Client[{}] entered the lobby without handshake", from.name);
+                return;
+            }
+        }
+        // the player takes the first free seat
+        let seat = match self.seats.iter().position(Option::is_none) {
+            Some(seat) => seat,
+            None => {
+                warn!("No seat left for [{}]", from.name);
+                self.sessions.remove(&addr);
+                let reply = ConnectionMessage::Rejected(RejectReason::LobbyFull);
+                let msg = TransMessage::connection(self.info.clone(), reply);
+                self.send(addr, msg, now);
+                return;
+            }
+        };
+        // tell the player how many players are online right now
+        let online: Vec<ClientInfo> = self.players.values().cloned().collect();
+        for c in online {
+            info!(
+                "Tell the player:[{}] that [{}] is in the game lobby.",
+                from.name, c.name
+            );
+            let msg =
+                TransMessage::connection(self.info.clone(), ConnectionMessage::PlayerEntered(c));
+            self.send(addr, msg, now);
+        }
+
+        // tell all other players that a new player has joined the game,
+        // the new player loads himself from the same message
+        info!("Tell all players that [{}] enter lobby.", from.name);
+        self.players.insert(addr, from.clone());
+        let name = from.name.clone();
+        let msg =
+            TransMessage::connection(self.info.clone(), ConnectionMessage::PlayerEntered(from));
+        self.broadcast(msg, now);
+
+        self.seats[seat] = Some(name);
+        self.broadcast_seats(now);
+    }
+
+    /// Move the player to another seat, or swap seats with the player sitting there.
+    fn choose_seat(&mut self, addr: SocketAddr, from: ClientInfo, seat: usize, now: Instant) {
+        if !self.players.contains_key(&addr) || self.game.is_some() {
+            debug!("Ignore the seat of [{}] outside the lobby", from.name);
+            return;
+        }
+        let mine = self
+            .seats
+            .iter()
+            .position(|s| s.as_deref() == Some(from.name.as_str()));
+        match mine {
+            Some(mine) if seat < self.seats.len() => {
+                info!("[{}] moves from seat {} to {}", from.name, mine, seat);
+                self.seats.swap(mine, seat);
+                self.broadcast_seats(now);
+            }
+            _ => {
+                warn!("[{}] cannot sit on seat {}", from.name, seat);
+                let text = format!("There is no seat {}", seat);
+                self.notice(addr, text, now);
+            }
+        }
+    }
+
+    /// Tell everybody who sits where.
+    fn broadcast_seats(&mut self, now: Instant) {
+        let seats = self.seats.clone();
+        let msg = TransMessage::lobby(self.info.clone(), LobbyMessage::Seats { seats });
+        self.broadcast(msg, now);
+    }
+
+    fn handle_message(
+        &mut self,
+        net: &mut Network,
+        addr: SocketAddr,
+        resp: TransMessage,
+        now: Instant,
+    ) {
+        let is_hello = matches!(
+            &resp,
+            TransMessage::Connection(Message {
+                body: ConnectionMessage::Hello(_),
+                ..
+            })
+        );
+        if !is_hello && !self.players.contains_key(&addr) && !self.accepted.contains_key(&addr) {
+            warn!("Ignore message from [{}] before handshake", addr);
+            return;
+        }
+        match resp {
+            TransMessage::Connection(m) => match m.body {
+                ConnectionMessage::Hello(hello) => self.handshake(net, addr, &m.from, hello, now),
+                ConnectionMessage::EnterLobby => self.enter_lobby(addr, m.from, now),
+                ConnectionMessage::Exit => self.drop_client(&addr, Departure::Left, now),
+                // The remote was marked as seen already
+                ConnectionMessage::Heartbeat => {}
+                body => debug!("Unhandled connection message {:?}", body),
+            },
+            // Notices only go from the server to the clients
+            TransMessage::System(m) => debug!("Ignore system message {:?} from [{}]", m.body, addr),
+            TransMessage::Lobby(m) => match m.body {
+                LobbyMessage::Prepare => self.prepare(addr, m.from, true, now),
+                LobbyMessage::CancelPrepare => self.prepare(addr, m.from, false, now),
+                LobbyMessage::ChooseCharacter { character } => {
+                    self.choose_character(addr, m.from, character, now)
+                }
+                LobbyMessage::ChooseSeat { seat } => self.choose_seat(addr, m.from, seat, now),
+                body => debug!("Unhandled lobby message {:?}", body),
+            },
+            TransMessage::Chat(m) => {
+                info!("Received: [ChatMessage]");
+
+                if self.chat_log.len() == CHAT_BACKLOG {
+                    self.chat_log.pop_front();
+                }
+                self.chat_log.push_back(m.clone());
+                let trans_message = TransMessage::chat(m.from, m.body.text);
+                debug!("ForwardChatMessage is {:?}", trans_message);
+                self.broadcast(trans_message, now);
+                info!("Sent: [ForwardChatMessage] to all clients");
+            }
+            TransMessage::Game(m) => match m.body {
+                GameMessage::Action(action) => self.handle_action(addr, action, now),
+                body => debug!("Unhandled game message {:?}", body),
+            },
+        }
+    }
+
+    /// Change the readiness of a player, and start a game once everybody is ready.
+    fn prepare(&mut self, addr: SocketAddr, from: ClientInfo, prepared: bool, now: Instant) {
+        if !self.players.contains_key(&addr) || self.game.is_some() {
+            debug!("Ignore readiness of [{}] outside the lobby", from.name);
+            return;
+        }
+        let changed = if prepared {
+            self.prepared.insert(addr)
+        } else {
+            self.prepared.remove(&addr)
+        };
+        if !changed {
+            return;
+        }
+        info!("[{}] prepared: {}", from.name, prepared);
+        let msg = TransMessage::lobby(
+            self.info.clone(),
+            LobbyMessage::PlayerPrepared {
+                player: from,
+                prepared,
+            },
+        );
+        self.broadcast(msg, now);
+        self.start_game(now);
+    }
+
+    /// Remember the character a player wants to play, several players may choose the same.
+    fn choose_character(
+        &mut self,
+        addr: SocketAddr,
+        from: ClientInfo,
+        character: Option<String>,
+        now: Instant,
+    ) {
+        if !self.players.contains_key(&addr) || self.game.is_some() {
+            debug!("Ignore the character of [{}] outside the lobby", from.name);
+            return;
+        }
+        match &character {
+            Some(name) if self.cards.character(name).is_none() => {
+                warn!("[{}] chose the unknown character {}", from.name, name);
+                let text = format!("There is no character {}", name);
+                self.notice(addr, text, now);
+                return;
+            }
+            Some(name) => self.characters.insert(addr, name.clone()),
+            None => self.characters.remove(&addr),
+        };
+        info!("[{}] plays {:?}", from.name, character);
+        let msg = TransMessage::lobby(
+            self.info.clone(),
+            LobbyMessage::CharacterChosen {
+                player: from,
+                character,
+            },
+        );
+        self.broadcast(msg, now);
+    }
+
+    /// Start a game once enough players are in the lobby and all of them are ready.
+    fn start_game(&mut self, now: Instant) {
+        if self.players.len() > self.rules.max_players {
+            info!(
+                "Too many players for a {} game: {}",
+                self.rules.name,
+                self.players.len()
+            );
+            return;
+        }
+        if self.players.len() < self.rules.min_players
+            || self
+                .players
+                .keys()
+                .any(|addr| !self.prepared.contains(addr))
+        {
+            return;
+        }
+        // The players take turns in the order they sit
+        let players: Vec<(String, Option<String>)> = self
+            .seats
+            .iter()
+            .flatten()
+            .filter_map(|name| self.addr_of(name))
+            .map(|addr| {
+                let player = &self.players[&addr];
+                (player.name.clone(), self.characters.get(&addr).cloned())
+            })
+            .collect();
+        // The seed, the seats and the actions are all it takes to play the game again
+        let seed = self.seed.unwrap_or_else(random);
+        info!("Start a game with {:?}, seed {}", players, seed);
+        let mut game = Engine::new(self.cards.clone(), players, self.rules.mode, seed);
+        let events = game.start();
+        let bank = Duration::from_secs(self.rules.time_bank.into());
+        self.banks = game
+            .state()
+            .players
+            .iter()
+            .map(|p| (p.name.clone(), bank))
+            .collect();
+        self.game = Some(game);
+        self.prepared.clear();
+        self.broadcast_events(events, now);
+    }
+
+    /// Let the engine carry out an action, and tell everybody what happened, or the sender
+    /// why nothing happened.
+    fn handle_action(&mut self, addr: SocketAddr, action: GameAction, now: Instant) {
+        let result = match (self.players.get(&addr), self.game.as_mut()) {
+            (Some(player), Some(game)) => {
+                info!("Received: [GameAction] {:?} from [{}]", action, player.name);
+                game.act(&player.name, action)
+            }
+            _ => Err(ActionError::NotPlaying),
+        };
+        match result {
+            Ok(events) => self.broadcast_events(events, now),
+            Err(reason) => {
+                info!("Reject the action of [{}]: {}", addr, reason);
+                let msg = TransMessage::game(self.info.clone(), GameMessage::Rejected(reason));
+                self.send(addr, msg, now);
+            }
+        }
+    }
+
+    /// The name the player views the running game with, `None` for a player without a seat in
+    /// it, who watches as a spectator.
+    fn viewer<'a>(&self, name: &'a str) -> Option<&'a str> {
+        match self.game.as_ref() {
+            Some(game) if game.state().seat_of(name).is_some() => Some(name),
+            _ => None,
+        }
+    }
+
+    /// Send the new events of the game, numbered by their place in the log of the game. Every
+    /// player is sent what the player may see of them, see `GameEvent::view`.
+    fn broadcast_events(&mut self, events: Vec<GameEvent>, now: Instant) {
+        let logged = self.game.as_ref().map_or(0, |game| game.log().len());
+        let first = logged - events.len();
+        let recipients: Vec<(SocketAddr, Option<String>)> = self
+            .players
+            .iter()
+            .map(|(addr, player)| (*addr, self.viewer(&player.name).map(str::to_string)))
+            .collect();
+        for (index, event) in events.iter().enumerate() {
+            let seq = (first + index) as u64;
+            for (addr, viewer) in &recipients {
+                if let Some(event) = event.view(viewer.as_deref()) {
+                    let body = GameMessage::Event { seq, event };
+                    let msg = TransMessage::game(self.info.clone(), body);
+                    self.send(*addr, msg, now);
+                }
+            }
+        }
+        self.set_clock(&events, now);
+        if let Some(true) = self.game.as_ref().map(Engine::is_over) {
+            // Back to the lobby, the players prepare for the next game
+            info!("The game is over");
+            self.game = None;
+        }
+    }
+
+    fn addr_of(&self, player: &str) -> Option<SocketAddr> {
+        self.players
+            .iter()
+            .find(|(_, p)| p.name == player)
+            .map(|(addr, _)| *addr)
+    }
+
+    /// Run the clock for whoever the game waits for after the events: the player asked to
+    /// respond, or else the current player in the play phase. The clock of the play phase
+    /// stops while somebody is asked and goes on where it stopped afterwards.
+    fn set_clock(&mut self, events: &[GameEvent], now: Instant) {
+        let game = match self.game.as_ref() {
+            Some(game) if !game.is_over() => game,
+            _ => {
+                self.stop_clock(now);
+                self.turn_left = None;
+                return;
+            }
+        };
+        let asked = events.iter().any(|e| {
+            matches!(
+                e,
+                GameEvent::ResponseRequested { .. } | GameEvent::DiscardRequested { .. }
+            )
+        });
+        let new_turn = events.iter().any(|e| {
+            matches!(
+                e,
+                GameEvent::PhaseChanged {
+                    phase: Phase::Play,
+                    ..
+                }
+            )
+        });
+        let waiting = game.prompt().map(|(player, _)| player.to_string());
+        let state = game.state();
+        let current = state.players[state.turn.current].name.clone();
+        let playing = state.turn.phase == Phase::Play;
+
+        if new_turn {
+            self.turn_left = None;
+        }
+        if asked {
+            if new_turn {
+                self.stop_clock(now);
+            } else {
+                self.pause_turn(now);
+            }
+            if let Some(player) = waiting {
+                let timeout = Duration::from_secs(self.rules.response_timeout.into());
+                self.wind_clock(player, false, timeout, now);
+                self.send_prompt(now);
+            }
+        } else if waiting.is_some() {
+            // Still waiting for the same answer
+        } else if playing {
+            let running = match &self.clock {
+                Some(clock) => clock.turn && clock.player == current && !new_turn,
+                None => false,
+            };
+            if !running {
+                self.stop_clock(now);
+                let timeout = Duration::from_secs(self.rules.turn_timeout.into());
+                let left = self.turn_left.take().unwrap_or(timeout);
+                self.wind_clock(current, true, left, now);
+            }
+        } else {
+            self.stop_clock(now);
+            self.turn_left = None;
+        }
+    }
+
+    fn wind_clock(&mut self, player: String, turn: bool, limit: Duration, now: Instant) {
+        self.clock = Some(Clock {
+            player,
+            turn,
+            deadline: now + limit,
+            banked_since: None,
+            shown: None,
+        });
+    }
+
+    /// Stop the clock of the play phase, it goes on from there once nobody is asked anymore.
+    /// Time taken from the bank stays in the bank and is not given back as normal time.
+    fn pause_turn(&mut self, now: Instant) {
+        if let Some(clock) = self.stop_clock(now) {
+            if clock.turn {
+                let left = match clock.banked_since {
+                    Some(_) => Duration::from_secs(0),
+                    None => clock.deadline.saturating_duration_since(now),
+                };
+                self.turn_left = Some(left);
+            }
+        }
+    }
+
+    /// Stop the clock, the player loses the part of the time bank that was used.
+    fn stop_clock(&mut self, now: Instant) -> Option<Clock> {
+        let clock = self.clock.take()?;
+        if let (Some(since), Some(bank)) = (clock.banked_since, self.banks.get_mut(&clock.player)) {
+            *bank = bank.checked_sub(now - since).unwrap_or_default();
+        }
+        Some(clock)
+    }
+
+    /// Ask the player the game waits for to respond.
+    fn send_prompt(&mut self, now: Instant) {
+        let (player, prompt) = match self.game.as_ref().and_then(Engine::prompt) {
+            Some((player, prompt)) => (player.to_string(), prompt),
+            None => return,
+        };
+        let timeout = self.rules.response_timeout;
+        match self.addr_of(&player) {
+            Some(addr) => {
+                let body = GameMessage::Prompt { prompt, timeout };
+                let msg = TransMessage::game(self.info.clone(), body);
+                self.send(addr, msg, now);
+            }
+            // The player may come back before the time is up
+            None => info!("[{}] is asked to respond but not connected", player),
+        }
+    }
+
+    /// Tell everybody how much time is left whenever another second is gone. Once the time
+    /// is up the player goes on with the time bank, if there is any left, or the game acts
+    /// for the player: pass, discard, or end the play phase.
+    fn check_clock(&mut self, now: Instant) {
+        let (player, deadline, banked) = match &self.clock {
+            Some(clock) => (clock.player.clone(), clock.deadline, clock.banked_since),
+            None => return,
+        };
+        if deadline <= now {
+            let bank = match banked {
+                Some(_) => None,
+                None => self.banks.get(&player).copied(),
+            };
+            match bank {
+                Some(bank) if bank > Duration::from_secs(0) => {
+                    info!("[{}] is out of time and uses the time bank", player);
+                    if let Some(clock) = self.clock.as_mut() {
+                        clock.banked_since = Some(now);
+                        clock.deadline = now + bank;
+                        clock.shown = None;
+                    }
+                }
+                _ => {
+                    self.stop_clock(now);
+                    if let Some(game) = self.game.as_mut() {
+                        let events = game.time_out();
+                        self.broadcast_events(events, now);
+                    }
+                    return;
+                }
+            }
+        }
+        let clock = match self.clock.as_mut() {
+            Some(clock) => clock,
+            None => return,
+        };
+        let left = clock.deadline.saturating_duration_since(now);
+        // Round up, the last second counts until it is over
+        let seconds = (left.as_secs() + u64::from(left.subsec_nanos() > 0)) as u32;
+        if clock.shown == Some(seconds) {
+            return;
+        }
+        clock.shown = Some(seconds);
+        let body = GameMessage::Timer {
+            player: clock.player.clone(),
+            seconds,
+            bank: clock.banked_since.is_some(),
+        };
+        self.broadcast(TransMessage::game(self.info.clone(), body), now);
+    }
+
+    /// Forget the connection of a client. The seat of a player is kept for the grace period
+    /// unless the player left on purpose.
+    fn drop_client(&mut self, addr: &SocketAddr, departure: Departure, now: Instant) {
+        self.accepted.remove(addr);
+        self.prepared.remove(addr);
+        self.characters.remove(addr);
+        self.remotes.remove(addr);
+        let session = self.sessions.remove(addr);
+        let player = match self.players.remove(addr) {
+            Some(player) => player,
+            None => return,
+        };
+        match session {
+            Some(token)
+                if departure != Departure::Left
+                    && self.reconnect_grace > Duration::from_secs(0) =>
+            {
+                info!("Keep the seat of [{}] for a reconnect", player.name);
+                self.suspended.insert(
+                    token,
+                    Suspended {
+                        player,
+                        since: now,
+                        departure,
+                    },
+                );
+            }
+            _ => self.announce_departure(player, departure, now),
+        }
+    }
+
+    /// Tell the other players that a player is gone for good, the seat is free again.
+    fn announce_departure(&mut self, player: ClientInfo, departure: Departure, now: Instant) {
+        for seat in self.seats.iter_mut() {
+            if seat.as_deref() == Some(player.name.as_str()) {
+                *seat = None;
+            }
+        }
+        let body = match departure {
+            Departure::TimedOut => ConnectionMessage::PlayerTimedOut(player),
+            Departure::Disconnected | Departure::Left => ConnectionMessage::PlayerExited(player),
+        };
+        let msg = TransMessage::connection(self.info.clone(), body);
+        self.broadcast(msg, now);
+        self.broadcast_seats(now);
+    }
+
+    /// Remove the clients that were not heard of for too long. Udp clients do not cause
+    /// disconnect events, so this is the only way to get rid of them.
+    fn remove_idle_clients(&mut self, now: Instant) {
+        let idle: Vec<SocketAddr> = self
+            .remotes
+            .iter()
+            .filter(|(_, remote)| remote.liveness.timed_out(now))
+            .map(|(addr, _)| *addr)
+            .collect();
+        for addr in idle {
+            info!("Client timed out: {}", addr);
+            self.drop_client(&addr, Departure::TimedOut, now);
+        }
+
+        let grace = self.reconnect_grace;
+        let expired: Vec<SessionToken> = self
+            .suspended
+            .iter()
+            .filter(|(_, s)| now.duration_since(s.since) >= grace)
+            .map(|(token, _)| *token)
+            .collect();
+        for token in expired {
+            let suspended = self.suspended.remove(&token).unwrap();
+            info!("[{}] did not come back", suspended.player.name);
+            self.announce_departure(suspended.player, suspended.departure, now);
+        }
+    }
+
+    /// Retransmit what is overdue, send everything queued for the clients and heartbeats to
+    /// those who were sent nothing for a while.
+    /// A client that does not acknowledge a message is dropped, its channel cannot go on
+    /// without the message. The player may take the seat again with a new connection.
+    fn flush(&mut self, net: &mut Network, now: Instant) {
+        let mut stalled = vec![];
+        for (addr, remote) in self.remotes.iter_mut() {
+            match remote.peer.update(now) {
+                Ok(()) => {}
+                Err(MessageError::Unacknowledged) => {
+                    warn!("Client [{}] does not acknowledge messages", addr);
+                    stalled.push(*addr);
+                }
+                Err(e) => warn!("Lost messages from or to client [{}]: {:?}", addr, e),
+            }
+            if remote.liveness.heartbeat_due(now) && !remote.peer.has_outgoing() {
+                let msg = TransMessage::connection(self.info.clone(), ConnectionMessage::Heartbeat);
+                remote.peer.send(msg, Delivery::Lossy, now);
+            }
+            send_datagrams(net, *addr, remote, now);
+        }
+        for addr in stalled {
+            self.drop_client(&addr, Departure::TimedOut, now);
+        }
+    }
+
+    /// Handle everything that arrived since the last tick and send the answers.
+    pub fn update(&mut self, net: &mut Network, now: Instant) {
+        for event in net.poll() {
+            match event {
+                NetworkEvent::Message(addr, payload) => {
+                    trace!("Datagram of {} bytes from [{}]", payload.len(), addr);
+                    // The handshake is always done in JSON
+                    let (transport, heartbeat) = (self.transport, self.heartbeat);
+                    let remote = self.remotes.entry(addr).or_insert_with(|| Remote {
+                        peer: Peer::new(CodecKind::Json, transport),
+                        liveness: Liveness::new(heartbeat, now),
+                    });
+                    let mut messages = vec![];
+                    if let Err(e) = remote.peer.receive(&payload, now, &mut messages) {
+                        // Most likely a client speaking another protocol version
+                        warn!(
+                            "Received messages that cannot be processed: {:?}, protocol version: {:?}",
+                            e,
+                            Hello::probe_version(&payload)
+                        );
+                        if remote.peer.is_broken() {
+                            warn!("The stream of client [{}] is corrupt, closing it", addr);
+                            net.close(addr);
+                        }
+                        if messages.is_empty() {
+                            continue;
+                        }
+                    }
+                    remote.liveness.seen(now);
+                    for resp in messages {
+                        self.handle_message(net, addr, resp, now);
+                    }
+                }
+                NetworkEvent::Connect(addr) => {
+                    info!("New client connection: {}", addr);
+                    self.connection.push(addr);
+                    self.online_num = self.connection.len() as u32;
+                    info!("Online player num: {:?}", self.online_num);
+                }
+                NetworkEvent::Disconnect(addr) => {
+                    info!("Client Disconnected: {}", addr);
+                    self.connection.retain(|x| *x != addr);
+                    self.online_num = self.connection.len() as u32;
+                    self.drop_client(&addr, Departure::Disconnected, now);
+
+                    info!("Online player num: {:?}", self.online_num);
+                }
+                NetworkEvent::RecvError(e) => {
+                    error!("Recv Error: {:?}", e);
+                }
+            }
+        }
+        self.remove_idle_clients(now);
+        self.check_clock(now);
+        self.flush(net, now);
+    }
+}
+
+/// Reply on the address the client's messages came from, which is the sending udp socket
+/// or the open tcp stream of the client.
+fn send_datagrams(net: &mut Network, addr: SocketAddr, remote: &mut Remote, now: Instant) {
+    let datagrams = remote.peer.drain_outgoing();
+    if !datagrams.is_empty() {
+        remote.liveness.sent(now);
+    }
+    for datagram in datagrams {
+        debug!("Send {} bytes to the client[{}]", datagram.len(), addr);
+        net.send(addr, &datagram);
+    }
+}
+
+/// An unpredictable number for session tokens and game seeds.
+fn random() -> u64 {
+    RandomState::new().build_hasher().finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shared::utilities::{
+        codec::decode_any,
+        fragment::Reassembler,
+        reliable::{Packet, PacketKind},
+    };
+    use std::net::UdpSocket;
+
+    fn config() -> ServiceConfig {
+        ServiceConfig {
+            name: "test".to_string(),
+            debug_wire: false,
+            heartbeat: HeartbeatConfig::default(),
+            reconnect_grace: Duration::from_secs(0),
+            cards: CardDatabase::default(),
+            seed: None,
+            response_timeout: 3,
+            turn_timeout: 10,
+            time_bank: 5,
+            mode: GameMode::Roles,
+            seats: None,
+        }
+    }
+
+    fn service() -> Service {
+        Service::new(config(), Transport::Udp)
+    }
+
+    /// A deck of plain cards, enough for the opening hands and a few turns.
+    fn cards() -> CardDatabase {
+        let copies: Vec<String> = (1..=13).map(|rank| format!("(Spade, {})", rank)).collect();
+        let ron = format!(
+            r#"(cards: [(name: "Attack", category: Basic, effects: ["damage"],
+                target: OtherInAttackRange, art: "", copies: [{}])])"#,
+            copies.join(", ")
+        );
+        CardDatabase::parse(&ron).unwrap()
+    }
+
+    fn network() -> Network {
+        Network::udp(UdpSocket::bind("127.0.0.1:0").unwrap(), 1500).unwrap()
+    }
+
+    fn address(port: u16) -> SocketAddr {
+        SocketAddr::from(([127, 0, 0, 1], port))
+    }
+
+    /// Let messages from the address arrive, the service only answers known remotes.
+    fn connect(service: &mut Service, addr: SocketAddr, now: Instant) {
+        let remote = Remote {
+            peer: Peer::new(CodecKind::Json, Transport::Udp),
+            liveness: Liveness::new(service.heartbeat, now),
+        };
+        service.remotes.insert(addr, remote);
+    }
+
+    /// The messages queued for the address, in order.
+    fn sent(service: &mut Service, addr: SocketAddr, now: Instant) -> Vec<TransMessage> {
+        let mut reassembler = Reassembler::default();
+        let datagrams = service
+            .remotes
+            .get_mut(&addr)
+            .unwrap()
+            .peer
+            .drain_outgoing();
+        datagrams
+            .iter()
+            .filter_map(|datagram| {
+                if Reassembler::is_fragment(datagram) {
+                    reassembler.insert(datagram, now).unwrap()
+                } else {
+                    Some(datagram.clone())
+                }
+            })
+            .map(|bytes| match decode_any::<Packet>(&bytes).unwrap().kind {
+                PacketKind::Reliable { msg, .. } | PacketKind::Lossy(msg) => msg,
+                PacketKind::Ack { .. } => panic!("The service sent an ack"),
+            })
+            .collect()
+    }
+
+    /// Let the players enter the lobby, the nth player from port n with session n.
+    fn seat(service: &mut Service, names: &[&str], now: Instant) {
+        for (index, name) in names.iter().enumerate() {
+            let addr = address(index as u16 + 1);
+            connect(service, addr, now);
+            service.accepted.insert(addr, name.to_string());
+            service.sessions.insert(addr, index as SessionToken + 1);
+            service.enter_lobby(addr, ClientInfo::new(name.to_string(), 0), now);
+        }
+    }
+
+    #[test]
+    fn seats_follow_the_flag() {
+        let config = ServiceConfig {
+            seats: Some(5),
+            ..config()
+        };
+        let service = Service::new(config, Transport::Udp);
+        assert_eq!(service.rules.max_players, 5);
+        assert_eq!(service.seats.len(), 5);
+    }
+
+    #[test]
+    fn duel_keeps_two_seats() {
+        let config = ServiceConfig {
+            mode: GameMode::Duel,
+            seats: Some(6),
+            ..config()
+        };
+        let service = Service::new(config, Transport::Udp);
+        assert_eq!(service.rules.max_players, 2);
+        assert_eq!(service.seats.len(), 2);
+    }
+
+    #[test]
+    fn bank_is_not_given_back_after_a_prompt() {
+        let mut service = service();
+        let start = Instant::now();
+        let secs = Duration::from_secs;
+        service.banks.insert("a".to_string(), secs(5));
+        service.wind_clock("a".to_string(), true, secs(10), start);
+
+        // The normal time is up, the bank takes over
+        service.check_clock(start + secs(10));
+        let clock = service.clock.as_ref().unwrap();
+        assert_eq!(clock.banked_since, Some(start + secs(10)));
+        assert_eq!(clock.deadline, start + secs(15));
+
+        // Somebody is asked two seconds into the bank
+        service.pause_turn(start + secs(12));
+        assert_eq!(service.banks["a"], secs(3));
+        assert_eq!(service.turn_left, Some(secs(0)));
+
+        // Back in the play phase only the rest of the bank is left
+        let resumed = start + secs(20);
+        let left = service.turn_left.take().unwrap();
+        service.wind_clock("a".to_string(), true, left, resumed);
+        service.check_clock(resumed);
+        let clock = service.clock.as_ref().unwrap();
+        assert_eq!(clock.deadline, resumed + secs(3));
+        service.check_clock(resumed + secs(3));
+        assert!(service.clock.is_none());
+    }
+
+    #[test]
+    fn normal_time_is_kept_over_a_prompt() {
+        let mut service = service();
+        let start = Instant::now();
+        let secs = Duration::from_secs;
+        service.banks.insert("a".to_string(), secs(5));
+        service.wind_clock("a".to_string(), true, secs(10), start);
+        service.pause_turn(start + secs(4));
+        assert_eq!(service.turn_left, Some(secs(6)));
+        assert_eq!(service.banks["a"], secs(5));
+    }
+
+    #[test]
+    fn resumed_seat_is_replayed_to_its_player_only() {
+        let config = ServiceConfig {
+            cards: cards(),
+            reconnect_grace: Duration::from_secs(30),
+            ..config()
+        };
+        let mut service = Service::new(config, Transport::Udp);
+        let now = Instant::now();
+        seat(&mut service, &["alice", "bob", "carol"], now);
+        for index in 1..=3 {
+            service.prepare(address(index), ClientInfo::default(), true, now);
+        }
+        assert!(service.game.is_some());
+
+        // Bob loses the connection and comes back with his session, but as alice
+        service.drop_client(&address(2), Departure::TimedOut, now);
+        let thief = address(4);
+        connect(&mut service, thief, now);
+        let hello = Hello {
+            resume: Some(2),
+            ..Hello::new(REQUIRED_CAPABILITIES.to_vec(), vec![CodecKind::Json])
+        };
+        let alice = ClientInfo::new("alice".to_string(), 0);
+        service.handshake(&mut network(), thief, &alice, hello, now);
+        assert_eq!(service.players[&thief].name, "bob");
+
+        let events = sent(&mut service, thief, now)
+            .into_iter()
+            .find_map(|msg| match msg {
+                TransMessage::Game(Message {
+                    body: GameMessage::Replay { events, .. },
+                    ..
+                }) => Some(events),
+                _ => None,
+            })
+            .unwrap();
+        let own = |e: &GameEvent| match e {
+            GameEvent::RoleAssigned { player, .. } => player == "bob",
+            _ => false,
+        };
+        assert!(events.iter().any(own));
+        for event in events {
+            match event {
+                GameEvent::CardsDrawn { player, cards, .. } => {
+                    assert_eq!(
+                        cards.is_empty(),
+                        player != "bob",
+                        "{} drew {:?}",
+                        player,
+                        cards
+                    )
+                }
+                GameEvent::RoleAssigned { player, role } => {
+                    assert!(
+                        player == "bob" || role.is_revealed(),
+                        "{} is {}",
+                        player,
+                        role
+                    )
+                }
+                _ => {}
+            }
+        }
+    }
+
+    #[test]
+    fn clients_in_the_handshake_count_against_the_seats() {
+        let config = ServiceConfig {
+            mode: GameMode::Duel,
+            ..config()
+        };
+        let mut service = Service::new(config, Transport::Udp);
+        let mut net = network();
+        let now = Instant::now();
+        // Everybody says hello before anybody enters the lobby
+        for (index, name) in ["alice", "bob", "carol"].iter().enumerate() {
+            let addr = address(index as u16 + 1);
+            connect(&mut service, addr, now);
+            let hello = Hello::new(REQUIRED_CAPABILITIES.to_vec(), vec![CodecKind::Json]);
+            let from = ClientInfo::new(name.to_string(), 0);
+            service.handshake(&mut net, addr, &from, hello, now);
+        }
+        assert_eq!(service.accepted.len(), 2);
+        assert!(!service.accepted.contains_key(&address(3)));
+
+        for (index, name) in ["alice", "bob"].iter().enumerate() {
+            let from = ClientInfo::new(name.to_string(), 0);
+            service.enter_lobby(address(index as u16 + 1), from, now);
+        }
+        assert_eq!(service.players.len(), 2);
+        assert!(service.seats.iter().all(Option::is_some));
+    }
+
+    #[test]
+    fn no_free_seat_turns_the_player_away() {
+        let config = ServiceConfig {
+            mode: GameMode::Duel,
+            ..config()
+        };
+        let mut service = Service::new(config, Transport::Udp);
+        let now = Instant::now();
+        seat(&mut service, &["alice", "bob", "carol"], now);
+        assert_eq!(service.players.len(), 2);
+        assert!(!service.players.contains_key(&address(3)));
+        let rejected = sent(&mut service, address(3), now).into_iter().any(|msg| {
+            matches!(
+                msg,
+                TransMessage::Connection(Message {
+                    body: ConnectionMessage::Rejected(RejectReason::LobbyFull),
+                    ..
+                })
+            )
+        });
+        assert!(rejected);
+    }
+
+    #[test]
+    fn refused_lobby_requests_are_noticed() {
+        let mut service = service();
+        let now = Instant::now();
+        seat(&mut service, &["alice"], now);
+        let addr = address(1);
+        sent(&mut service, addr, now);
+
+        let alice = ClientInfo::new("alice".to_string(), 0);
+        service.choose_seat(addr, alice.clone(), 9, now);
+        let character = Some("Nobody".to_string());
+        service.choose_character(addr, alice, character, now);
+        let notices: Vec<String> = sent(&mut service, addr, now)
+            .into_iter()
+            .filter_map(|msg| match msg {
+                TransMessage::System(Message {
+                    body: SystemMessage::Notice(text),
+                    ..
+                }) => Some(text),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            notices,
+            ["There is no seat 9", "There is no character Nobody"]
+        );
+        assert!(!service.characters.contains_key(&addr));
+    }
+}
@@ -1,6 +1,7 @@
 // fn main() {
 //     println!("Hello, world!");
 // }
+pub mod rules;
 pub mod utilities;
 
 pub use utilities::*;
@@ -0,0 +1,71 @@
+use serde::{Deserialize, Serialize};
+use std::fmt::{self, Display, Formatter};
+
+/// Identifies a single physical card within a game.
+pub type CardId = u32;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Suit {
+    Spade,
+    Heart,
+    Club,
+    Diamond,
+}
+
+impl Suit {
+    pub fn is_red(self) -> bool {
+        matches!(self, Suit::Heart | Suit::Diamond)
+    }
+}
+
+/// The slots in front of a player that equipment cards go into.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EquipSlot {
+    // Different weapons have different effects
+    Weapon,
+    // Different armors have different effects
+    Armor,
+    // Increases your calculated distance from the enemy
+    OffendHorse,
+    // Reduces enemy's calculated distance from you
+    DefendHorse,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Category {
+    /// Attacks, dodges and heals.
+    Basic,
+    /// Resolved right away when played.
+    Trick,
+    /// Placed in front of a player and resolved in the judgment phase.
+    DelayedTrick,
+    /// Stays in front of the player in the given slot.
+    Equipment(EquipSlot),
+}
+
+/// A single physical card of a game.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct Card {
+    pub id: CardId,
+    pub name: String,
+    pub suit: Suit,
+    /// 1 (ace) to 13 (king)
+    pub rank: u8,
+    pub category: Category,
+}
+
+impl Card {
+    /// The slot an equipment card goes into.
+    pub fn slot(&self) -> Option<EquipSlot> {
+        match self.category {
+            Category::Equipment(slot) => Some(slot),
+            _ => None,
+        }
+    }
+}
+
+impl Display for Card {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}({:?} {})", self.name, self.suit, self.rank)
+    }
+}
@@ -0,0 +1,79 @@
+use serde::{Deserialize, Serialize};
+
+use super::{database::CardDatabase, game::GameState, player::Seat};
+
+/// How a skill comes into play.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SkillKind {
+    /// Always in effect, it changes how the rules apply to the owner.
+    Passive,
+    /// Used on its own when the trigger happens to the owner.
+    Triggered(Trigger),
+    /// Used by the owner in the play phase, once per turn.
+    Active,
+}
+
+/// Moments of the game triggered skills hook into.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Trigger {
+    /// The owner lost health.
+    Damaged,
+    /// The owner made another player lose health.
+    DealtDamage,
+    /// A delayed trick in front of the owner is about to hit with the flipped judgment.
+    Judgment,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct SkillDefinition {
+    pub name: String,
+    pub kind: SkillKind,
+    /// Identifier of what the skill does, interpreted by the engine like card effects
+    pub effect: String,
+    /// Shown to the players
+    pub description: String,
+}
+
+/// A character a player can choose. It gives the player its health and skills.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct CharacterDefinition {
+    pub name: String,
+    pub health: i32,
+    #[serde(default)]
+    pub skills: Vec<SkillDefinition>,
+}
+
+impl GameState {
+    /// Give the player the character with the given name, and the health that comes with
+    /// it. Unknown characters are ignored.
+    pub fn assign_character(&mut self, database: &CardDatabase, seat: Seat, character: &str) {
+        if let Some(definition) = database.character(character) {
+            let player = &mut self.players[seat];
+            player.character = Some(definition.name.clone());
+            player.max_health = definition.health;
+            player.health = definition.health;
+        }
+    }
+
+    /// The skills of the character of the player.
+    pub fn skills<'a>(
+        &self,
+        database: &'a CardDatabase,
+        seat: Seat,
+    ) -> impl Iterator<Item = &'a SkillDefinition> {
+        self.players[seat]
+            .character
+            .as_ref()
+            .and_then(|name| database.character(name))
+            .into_iter()
+            .flat_map(|definition| definition.skills.iter())
+    }
+
+    /// Whether the player has a skill with the given effect.
+    pub fn has_skill(&self, database: &CardDatabase, seat: Seat, effect: &str) -> bool {
+        self.skills(database, seat)
+            .any(|skill| skill.effect == effect)
+    }
+}
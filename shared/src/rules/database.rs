@@ -0,0 +1,204 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashSet,
+    fmt::{self, Display, Formatter},
+    fs, io,
+    path::Path,
+};
+
+use super::{
+    card::{Card, CardId, Category, Suit},
+    character::CharacterDefinition,
+};
+
+/// Who a card can be played on.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TargetRule {
+    /// Only played in answer to another card, it has no target of its own.
+    Response,
+    /// The player who plays it.
+    Myself,
+    /// Another player of choice.
+    Other,
+    /// Another player within the attack range of the player who plays it.
+    OtherInAttackRange,
+    /// Every other player, in seat order.
+    AllOthers,
+    /// Every player, in seat order starting with the one who plays it.
+    Everyone,
+}
+
+/// How a kind of card looks and behaves. Every copy in the deck shares the definition, only
+/// suit and rank differ.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct CardDefinition {
+    pub name: String,
+    pub category: Category,
+    /// Identifiers of what happens when the card resolves, in order
+    #[serde(default)]
+    pub effects: Vec<String>,
+    pub target: TargetRule,
+    /// For weapons the attack range they give, for other cards the farthest distance a
+    /// target may be at. `None` means no limit.
+    #[serde(default)]
+    pub range: Option<u32>,
+    /// Texture of the card face, relative to the assets directory
+    pub art: String,
+    /// Suit and rank of every copy in the deck
+    pub copies: Vec<(Suit, u8)>,
+}
+
+#[derive(Debug)]
+pub enum DatabaseError {
+    Io(io::Error),
+    Parse(String),
+    /// The file parsed, but describes cards that cannot exist
+    Invalid(String),
+}
+
+impl Display for DatabaseError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            DatabaseError::Io(e) => write!(f, "{}", e),
+            DatabaseError::Parse(e) => write!(f, "{}", e),
+            DatabaseError::Invalid(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for DatabaseError {}
+
+impl From<io::Error> for DatabaseError {
+    fn from(e: io::Error) -> Self {
+        DatabaseError::Io(e)
+    }
+}
+
+impl From<ron::de::Error> for DatabaseError {
+    fn from(e: ron::de::Error) -> Self {
+        DatabaseError::Parse(e.to_string())
+    }
+}
+
+/// All cards and characters a game is played with, read from `assets/config/cards.ron`. The
+/// client and the server load the same file, so balance changes and new cards are a data edit.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct CardDatabase {
+    cards: Vec<CardDefinition>,
+    #[serde(default)]
+    characters: Vec<CharacterDefinition>,
+}
+
+impl CardDatabase {
+    pub fn load(path: &Path) -> Result<Self, DatabaseError> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    pub fn parse(ron: &str) -> Result<Self, DatabaseError> {
+        let database: CardDatabase = ron::de::from_str(ron)?;
+        database.validate()?;
+        Ok(database)
+    }
+
+    fn validate(&self) -> Result<(), DatabaseError> {
+        let mut names = HashSet::new();
+        for definition in &self.cards {
+            if !names.insert(definition.name.as_str()) {
+                return Err(DatabaseError::Invalid(format!(
+                    "{} is defined twice",
+                    definition.name
+                )));
+            }
+            if let Some((suit, rank)) = definition
+                .copies
+                .iter()
+                .find(|(_, rank)| *rank < 1 || *rank > 13)
+            {
+                return Err(DatabaseError::Invalid(format!(
+                    "{} has a copy of rank {} of {:?}, ranks go from 1 to 13",
+                    definition.name, rank, suit
+                )));
+            }
+        }
+        let mut characters = HashSet::new();
+        for character in &self.characters {
+            if !characters.insert(character.name.as_str()) {
+                return Err(DatabaseError::Invalid(format!(
+                    "The character {} is defined twice",
+                    character.name
+                )));
+            }
+            if character.health < 1 {
+                return Err(DatabaseError::Invalid(format!(
+                    "The character {} has no health",
+                    character.name
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn definitions(&self) -> &[CardDefinition] {
+        &self.cards
+    }
+
+    pub fn get(&self, name: &str) -> Option<&CardDefinition> {
+        self.cards.iter().find(|d| d.name == name)
+    }
+
+    pub fn characters(&self) -> &[CharacterDefinition] {
+        &self.characters
+    }
+
+    pub fn character(&self, name: &str) -> Option<&CharacterDefinition> {
+        self.characters.iter().find(|c| c.name == name)
+    }
+
+    /// The definition of a card in the deck.
+    pub fn definition(&self, card: &Card) -> Option<&CardDefinition> {
+        self.get(&card.name)
+    }
+
+    /// Number of cards in a full deck.
+    pub fn deck_size(&self) -> usize {
+        self.cards.iter().map(|d| d.copies.len()).sum()
+    }
+
+    /// The card with the given id in the deck, see `deck`.
+    pub fn card(&self, id: CardId) -> Option<Card> {
+        let mut first = 0;
+        for definition in &self.cards {
+            let index = id as usize - first;
+            if let Some((suit, rank)) = definition.copies.get(index) {
+                return Some(Card {
+                    id,
+                    name: definition.name.clone(),
+                    suit: *suit,
+                    rank: *rank,
+                    category: definition.category,
+                });
+            }
+            first += definition.copies.len();
+        }
+        None
+    }
+
+    /// One card for every copy, numbered in the order of the file. The ids are the same on
+    /// every machine that loaded the same file.
+    pub fn deck(&self) -> Vec<Card> {
+        self.cards
+            .iter()
+            .flat_map(|d| d.copies.iter().map(move |copy| (d, copy)))
+            .enumerate()
+            .map(|(id, (definition, (suit, rank)))| Card {
+                id: id as CardId,
+                name: definition.name.clone(),
+                suit: *suit,
+                rank: *rank,
+                category: definition.category,
+            })
+            .collect()
+    }
+}
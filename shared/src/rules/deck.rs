@@ -0,0 +1,110 @@
+use serde::{Deserialize, Serialize};
+
+use super::{card::Card, rng::Rng, zone::CardPile};
+
+/// The draw pile and the discard pile. Cards are drawn from the top of the draw pile, and
+/// once it runs out the discard pile is shuffled into a new draw pile.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct Deck {
+    pub draw_pile: CardPile,
+    pub discard_pile: CardPile,
+    rng: Rng,
+}
+
+impl Deck {
+    /// Shuffle the cards into a new draw pile. The same seed always gives the same order.
+    pub fn new(mut cards: Vec<Card>, seed: u64) -> Self {
+        let mut rng = Rng::new(seed);
+        rng.shuffle(&mut cards);
+        Self {
+            draw_pile: CardPile::new(cards),
+            discard_pile: CardPile::default(),
+            rng,
+        }
+    }
+
+    /// Pick one of `count` things at random, with the numbers the shuffles come from.
+    pub fn pick(&mut self, count: usize) -> usize {
+        self.rng.below(count)
+    }
+
+    /// Shuffle anything else that needs to be random, with the numbers the shuffles of the
+    /// deck come from.
+    pub fn shuffle<T>(&mut self, items: &mut [T]) {
+        self.rng.shuffle(items);
+    }
+
+    /// Whether the next draw makes the discard pile the new draw pile.
+    pub fn needs_reshuffle(&self) -> bool {
+        self.draw_pile.is_empty() && !self.discard_pile.is_empty()
+    }
+
+    /// Shuffle the discard pile into the draw pile. Returns the number of cards shuffled.
+    pub fn reshuffle(&mut self) -> usize {
+        let mut cards = self.discard_pile.take_all();
+        self.rng.shuffle(&mut cards);
+        let count = cards.len();
+        for card in cards {
+            self.draw_pile.push(card);
+        }
+        count
+    }
+
+    /// Take the top card of the draw pile, reshuffling the discard pile if it is empty.
+    /// `None` only if there is no card left in either pile.
+    pub fn draw(&mut self) -> Option<Card> {
+        if self.needs_reshuffle() {
+            self.reshuffle();
+        }
+        self.draw_pile.pop()
+    }
+
+    pub fn discard(&mut self, card: Card) {
+        self.discard_pile.push(card);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::card::{CardId, Category, Suit};
+
+    fn cards() -> Vec<Card> {
+        (0..40)
+            .map(|id| Card {
+                id,
+                name: "Attack".to_string(),
+                suit: Suit::Spade,
+                rank: 1 + (id % 13) as u8,
+                category: Category::Basic,
+            })
+            .collect()
+    }
+
+    fn order(deck: &Deck) -> Vec<CardId> {
+        deck.draw_pile.cards().iter().map(|c| c.id).collect()
+    }
+
+    #[test]
+    fn same_seed_gives_same_order() {
+        assert_eq!(order(&Deck::new(cards(), 3)), order(&Deck::new(cards(), 3)));
+    }
+
+    #[test]
+    fn different_seeds_give_different_orders() {
+        assert_ne!(order(&Deck::new(cards(), 3)), order(&Deck::new(cards(), 4)));
+    }
+
+    #[test]
+    fn reshuffles_follow_the_seed() {
+        let reshuffled = |seed| {
+            let mut deck = Deck::new(cards(), seed);
+            while let Some(card) = deck.draw_pile.pop() {
+                deck.discard(card);
+            }
+            assert_eq!(deck.reshuffle(), 40);
+            order(&deck)
+        };
+        assert_eq!(reshuffled(3), reshuffled(3));
+    }
+}
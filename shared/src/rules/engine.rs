@@ -0,0 +1,1264 @@
+use serde::{Deserialize, Serialize};
+use std::fmt::{self, Display, Formatter};
+
+use std::collections::VecDeque;
+
+use super::{
+    card::{Card, CardId, Category},
+    database::{CardDatabase, TargetRule},
+    event::GameEvent,
+    game::GameState,
+    player::Seat,
+    role::{self, GameMode, Role},
+    turn::{Phase, TurnState},
+    zone::Zone,
+};
+use crate::msg::{GameAction, Prompt};
+
+mod health;
+mod judgment;
+mod skills;
+mod window;
+
+pub use judgment::LIGHTNING_DAMAGE;
+use window::{Effect, Window};
+
+/// Number of cards every player is dealt when the game starts.
+pub const OPENING_HAND: usize = 4;
+/// Number of cards a player draws in the draw phase.
+pub const DRAW_PHASE_CARDS: usize = 2;
+/// With more players than this the leader gets one more health.
+pub const LEADER_BONUS_FROM: usize = 4;
+/// Effects the engine cannot carry out yet, cards with them cannot be played.
+const UNRESOLVED_EFFECTS: &[&str] = &["borrow_weapon", "harvest"];
+
+/// Why the engine refused an action. The sender is told, nobody else.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub enum ActionError {
+    /// No game is running, or the sender does not play in it.
+    NotPlaying,
+    NotYourTurn,
+    /// The action is not allowed in the given phase.
+    WrongPhase(Phase),
+    /// The card is not in the hand of the sender.
+    NotInHand(CardId),
+    /// The card is only played in answer to another card.
+    NotPlayable(CardId),
+    /// The named player is not at the table or already dead.
+    UnknownTarget(String),
+    /// The targets do not match the target rule of the card.
+    InvalidTargets,
+    /// The named player is too far away for the card.
+    OutOfRange(String),
+    /// Nothing is waiting for this kind of answer.
+    Unexpected,
+    /// The game is decided, nothing can be done anymore.
+    GameOver,
+    /// The game waits for the named player to respond first.
+    WaitingFor(String),
+    /// The character of the player has no active skill of this name.
+    UnknownSkill(String),
+    /// The skill can be used once per turn only.
+    SkillUsed(String),
+    /// The player has to discard exactly this many cards to end the discard phase.
+    MustDiscard(u32),
+}
+
+impl Display for ActionError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            ActionError::NotPlaying => write!(f, "You are not playing"),
+            ActionError::NotYourTurn => write!(f, "It is not your turn"),
+            ActionError::WrongPhase(phase) => write!(f, "Not allowed in the {:?} phase", phase),
+            ActionError::NotInHand(card) => write!(f, "Card {} is not in your hand", card),
+            ActionError::NotPlayable(card) => write!(f, "Card {} cannot be played now", card),
+            ActionError::UnknownTarget(name) => write!(f, "{} cannot be targeted", name),
+            ActionError::InvalidTargets => write!(f, "The card does not take these targets"),
+            ActionError::OutOfRange(name) => write!(f, "{} is out of range", name),
+            ActionError::Unexpected => write!(f, "Nothing is waiting for this"),
+            ActionError::GameOver => write!(f, "The game is over"),
+            ActionError::WaitingFor(name) => write!(f, "Waiting for {} to respond", name),
+            ActionError::UnknownSkill(name) => write!(f, "You do not have the skill {}", name),
+            ActionError::SkillUsed(name) => write!(f, "{} was used this turn already", name),
+            ActionError::MustDiscard(count) => write!(f, "You have to discard {} cards", count),
+        }
+    }
+}
+
+/// Runs a game: checks the actions of the players against the rules and decides what
+/// happens. Every change is a `GameEvent`, which is applied to the state and kept in the log
+/// of the game, so the log replays the game from the start. Phases that need no decision
+/// of the player are run right away, the engine only waits in the play phase, in the discard
+/// phase of a player over the hand limit, and for the answers of players who are asked to
+/// respond.
+#[derive(Debug, Clone)]
+pub struct Engine {
+    state: GameState,
+    database: CardDatabase,
+    // Everything that happened, in order
+    log: Vec<GameEvent>,
+    mode: GameMode,
+    // Who won, once the game is over
+    winners: Option<Vec<Seat>>,
+    // Players asked to respond, everything else waits for them
+    window: Option<Window>,
+    // Effects of the played card still to be carried out, in order
+    effects: VecDeque<Effect>,
+    // Whether the current automatic phase did its work already
+    phase_done: bool,
+    // Active skills the current player used this turn
+    skills_used: Vec<String>,
+    // Whether the current player played an Attack this turn
+    attacked: bool,
+    // Phases of the current turn the judgments took away
+    skipped: Vec<Phase>,
+}
+
+impl Engine {
+    /// Seat the players in the given order with the characters they chose. The roles of
+    /// the mode are dealt at random and the leader takes the first turn, without roles the
+    /// first player does. The deck and the roles are shuffled with the seed, so the same seed
+    /// and the same actions give the same game.
+    pub fn new(
+        database: CardDatabase,
+        players: Vec<(String, Option<String>)>,
+        mode: GameMode,
+        seed: u64,
+    ) -> Self {
+        let (names, characters): (Vec<String>, Vec<Option<String>>) = players.into_iter().unzip();
+        let mut state = GameState::new(names, database.deck(), seed);
+        for (seat, character) in characters.iter().enumerate() {
+            if let Some(character) = character {
+                state.assign_character(&database, seat, character);
+            }
+        }
+        let mut roles = role::roles(mode, state.players.len());
+        state.deck.shuffle(&mut roles);
+        for (player, role) in state.players.iter_mut().zip(roles) {
+            player.role = Some(role);
+        }
+        if let Some(leader) = state.leader() {
+            // The leader has to hold out against everybody in bigger games
+            if state.players.len() > LEADER_BONUS_FROM {
+                state.players[leader].max_health += 1;
+                state.players[leader].health += 1;
+            }
+            state.turn = TurnState::new(leader);
+        }
+        Self {
+            state,
+            database,
+            log: Vec::new(),
+            mode,
+            winners: None,
+            window: None,
+            effects: VecDeque::new(),
+            phase_done: false,
+            skills_used: Vec::new(),
+            attacked: false,
+            skipped: Vec::new(),
+        }
+    }
+
+    pub fn state(&self) -> &GameState {
+        &self.state
+    }
+
+    pub fn database(&self) -> &CardDatabase {
+        &self.database
+    }
+
+    pub fn is_over(&self) -> bool {
+        self.winners.is_some()
+    }
+
+    /// All events of the game so far, in order.
+    pub fn log(&self) -> &[GameEvent] {
+        &self.log
+    }
+
+    /// Apply the event to the state and record it.
+    fn emit(&mut self, event: GameEvent) {
+        self.state.apply(&event, &self.database);
+        self.log.push(event);
+    }
+
+    /// The events recorded after the log had the given length.
+    fn events_since(&self, mark: usize) -> Vec<GameEvent> {
+        self.log[mark..].to_vec()
+    }
+
+    /// End the game if the death decided it.
+    fn check_victory(&mut self) {
+        let winners = match self.state.winners(self.mode) {
+            Some(winners) => winners,
+            None => return,
+        };
+        let names = winners
+            .iter()
+            .map(|seat| self.state.players[*seat].name.clone())
+            .collect();
+        self.emit(GameEvent::GameOver {
+            winners: names,
+            roles: self
+                .state
+                .players
+                .iter()
+                .map(|p| (p.name.clone(), p.role))
+                .collect(),
+        });
+        log::info!("The game is over, the winners are {:?}", winners);
+        self.winners = Some(winners);
+        self.window = None;
+        self.effects.clear();
+    }
+
+    /// Deal the opening hands and start the first turn.
+    pub fn start(&mut self) -> Vec<GameEvent> {
+        let mark = self.log.len();
+        let players = &self.state.players;
+        self.emit(GameEvent::GameStarted {
+            players: players.iter().map(|p| p.name.clone()).collect(),
+            characters: players.iter().map(|p| p.character.clone()).collect(),
+            health: players.iter().map(|p| p.health).collect(),
+        });
+        let roles: Vec<(String, Role)> = self
+            .state
+            .players
+            .iter()
+            .filter_map(|p| p.role.map(|role| (p.name.clone(), role)))
+            .collect();
+        for (player, role) in roles {
+            self.emit(GameEvent::RoleAssigned { player, role });
+        }
+        for seat in 0..self.state.players.len() {
+            self.draw(seat, OPENING_HAND);
+        }
+        self.enter_phase(self.state.turn);
+        self.run_automatic_phases();
+        self.events_since(mark)
+    }
+
+    /// Carry out an action of the named player, or tell why it is not allowed. Nothing is
+    /// changed if the action is rejected.
+    pub fn act(&mut self, player: &str, action: GameAction) -> Result<Vec<GameEvent>, ActionError> {
+        if self.is_over() {
+            return Err(ActionError::GameOver);
+        }
+        let seat = match self.state.seat_of(player) {
+            Some(seat) if self.state.players[seat].alive => seat,
+            _ => return Err(ActionError::NotPlaying),
+        };
+        let mark = self.log.len();
+        if let Some(asked) = self.asked() {
+            match action {
+                GameAction::Respond { card } if seat == asked => self.respond(seat, card)?,
+                _ => {
+                    let name = self.state.players[asked].name.clone();
+                    return Err(ActionError::WaitingFor(name));
+                }
+            }
+            self.proceed();
+            return Ok(self.events_since(mark));
+        }
+        if seat != self.state.turn.current {
+            return Err(ActionError::NotYourTurn);
+        }
+        match action {
+            GameAction::PlayCard { card, targets } => {
+                self.expect_phase(Phase::Play)?;
+                self.play_card(seat, card, targets)?;
+                self.proceed();
+            }
+            GameAction::Discard { cards } => {
+                self.expect_phase(Phase::Discard)?;
+                self.discard(seat, cards)?;
+                self.next_phase();
+            }
+            GameAction::UseSkill {
+                skill,
+                cards,
+                targets,
+            } => {
+                self.expect_phase(Phase::Play)?;
+                self.use_skill(seat, skill, cards, targets)?;
+                self.proceed();
+            }
+            GameAction::EndPhase => match self.state.turn.phase {
+                Phase::Discard if self.excess_cards(seat) > 0 => {
+                    return Err(ActionError::MustDiscard(self.excess_cards(seat) as u32))
+                }
+                Phase::Play | Phase::Discard => self.next_phase(),
+                phase => return Err(ActionError::WrongPhase(phase)),
+            },
+            GameAction::Respond { .. } => return Err(ActionError::Unexpected),
+        }
+        Ok(self.events_since(mark))
+    }
+
+    /// Let the player the game waits for take the default action because the player took
+    /// too long: pass, discard the cards drawn last, or end the play phase.
+    pub fn time_out(&mut self) -> Vec<GameEvent> {
+        let mark = self.log.len();
+        if let Some(asked) = self.asked() {
+            let name = &self.state.players[asked].name;
+            log::info!("{} did not respond in time", name);
+            if self.respond(asked, None).is_ok() {
+                self.proceed();
+            }
+        } else if let Some(Prompt::Discard { count, hand }) = self.prompt().map(|(_, p)| p) {
+            let seat = self.state.turn.current;
+            log::info!("{} did not discard in time", self.state.players[seat].name);
+            let cards = hand[hand.len() - count as usize..].to_vec();
+            if self.discard(seat, cards).is_ok() {
+                self.next_phase();
+            }
+        } else if self.state.turn.phase == Phase::Play && !self.is_over() {
+            let seat = self.state.turn.current;
+            log::info!(
+                "{} did not end the play phase in time",
+                self.state.players[seat].name
+            );
+            self.next_phase();
+        }
+        self.events_since(mark)
+    }
+
+    fn expect_phase(&self, phase: Phase) -> Result<(), ActionError> {
+        if self.state.turn.phase == phase {
+            Ok(())
+        } else {
+            Err(ActionError::WrongPhase(self.state.turn.phase))
+        }
+    }
+
+    fn play_card(
+        &mut self,
+        seat: Seat,
+        card: CardId,
+        targets: Vec<String>,
+    ) -> Result<(), ActionError> {
+        let played = self.state.players[seat]
+            .hand
+            .cards()
+            .iter()
+            .find(|c| c.id == card)
+            .cloned()
+            .ok_or(ActionError::NotInHand(card))?;
+        if UNRESOLVED_EFFECTS
+            .iter()
+            .any(|e| self.has_effect(&played, e))
+        {
+            return Err(ActionError::NotPlayable(card));
+        }
+        let seats = self.check_targets(seat, &played, &targets)?;
+        if self.has_effect(&played, "heal") && !self.state.players[seat].is_wounded() {
+            return Err(ActionError::NotPlayable(card));
+        }
+        let attack = self.is_attack(&played);
+        if attack && self.attacked && !self.unlimited_attacks(seat) {
+            return Err(ActionError::NotPlayable(card));
+        }
+        let takes_card =
+            self.has_effect(&played, "discard_one") || self.has_effect(&played, "steal_one");
+        if takes_card && seats.iter().any(|s| self.cards_of(*s).is_empty()) {
+            return Err(ActionError::InvalidTargets);
+        }
+
+        let player = self.state.players[seat].name.clone();
+        if let Some(slot) = played.slot() {
+            let replaced = self.state.players[seat].equipment.slot(slot).map(|c| c.id);
+            self.emit(GameEvent::Equipped {
+                player,
+                card,
+                replaced,
+            });
+            return Ok(());
+        }
+        if played.category == Category::DelayedTrick {
+            // Takes effect in the judgment phase of the target
+            return self.place_trick(seat, &played, seats[0]);
+        }
+        self.attacked |= attack;
+        self.emit(GameEvent::CardPlayed {
+            player,
+            card,
+            targets,
+        });
+        self.resolve(seat, &played, &seats);
+        Ok(())
+    }
+
+    /// Whether the definition of the card lists the effect.
+    pub(super) fn has_effect(&self, card: &Card, effect: &str) -> bool {
+        match self.database.definition(card) {
+            Some(definition) => definition.effects.iter().any(|e| e == effect),
+            None => false,
+        }
+    }
+
+    /// Queue the effects of a played card on each of its targets.
+    fn resolve(&mut self, seat: Seat, card: &Card, targets: &[Seat]) {
+        let effects = match self.database.definition(card) {
+            Some(definition) => definition.effects.clone(),
+            None => return,
+        };
+        for effect in effects {
+            for target in targets {
+                self.effects
+                    .push_back(Effect::new(seat, card, *target, &effect));
+            }
+        }
+    }
+
+    /// Carry out the queued effects until somebody is asked to respond, then go on with
+    /// the turn, or with the next one if the current player died.
+    fn proceed(&mut self) {
+        while self.window.is_none() && !self.is_over() {
+            match self.effects.pop_front() {
+                Some(effect) => self.start_effect(effect),
+                None => break,
+            }
+        }
+        self.run_automatic_phases();
+    }
+
+    /// Check the chosen targets against the target rule and the range of the card, and
+    /// return the seats the card affects.
+    fn check_targets(
+        &self,
+        seat: Seat,
+        card: &Card,
+        targets: &[String],
+    ) -> Result<Vec<Seat>, ActionError> {
+        let rule = self
+            .database
+            .definition(card)
+            .map(|d| d.target)
+            .ok_or(ActionError::NotPlayable(card.id))?;
+        let mut seats = Vec::with_capacity(targets.len());
+        for name in targets {
+            match self.state.seat_of(name) {
+                Some(target) if self.state.players[target].alive => seats.push(target),
+                _ => return Err(ActionError::UnknownTarget(name.clone())),
+            }
+        }
+        match (rule, seats.as_slice()) {
+            (TargetRule::Response, _) => Err(ActionError::NotPlayable(card.id)),
+            (TargetRule::Myself, []) => Ok(vec![seat]),
+            (TargetRule::Myself, [target])
+            | (TargetRule::Other, [target])
+            | (TargetRule::OtherInAttackRange, [target]) => self
+                .state
+                .can_target(&self.database, seat, *target, card)
+                .map(|()| vec![*target]),
+            // Everybody concerned is affected, there is nothing to choose
+            (TargetRule::AllOthers, []) => Ok(self.seat_order(seat).skip(1).collect()),
+            (TargetRule::Everyone, []) => Ok(self.seat_order(seat).collect()),
+            _ => Err(ActionError::InvalidTargets),
+        }
+    }
+
+    /// The living players in seat order, starting with the given seat.
+    fn seat_order(&self, first: Seat) -> impl Iterator<Item = Seat> + '_ {
+        let count = self.state.players.len();
+        (0..count)
+            .map(move |offset| (first + offset) % count)
+            .filter(move |seat| self.state.players[*seat].alive)
+    }
+
+    /// Discard down to the hand limit, with exactly as many cards as are over it. A card
+    /// named twice counts once.
+    fn discard(&mut self, seat: Seat, mut cards: Vec<CardId>) -> Result<(), ActionError> {
+        let mut seen = Vec::with_capacity(cards.len());
+        cards.retain(|card| {
+            let new = !seen.contains(card);
+            seen.push(*card);
+            new
+        });
+        let hand = &self.state.players[seat].hand;
+        if let Some(missing) = cards.iter().find(|c| !hand.contains(**c)) {
+            return Err(ActionError::NotInHand(*missing));
+        }
+        let excess = self.excess_cards(seat);
+        if cards.len() != excess {
+            return Err(ActionError::MustDiscard(excess as u32));
+        }
+        self.emit(GameEvent::CardsDiscarded {
+            player: self.state.players[seat].name.clone(),
+            cards,
+        });
+        Ok(())
+    }
+
+    /// Number of cards the player holds over the hand limit.
+    fn excess_cards(&self, seat: Seat) -> usize {
+        let hand = self.state.players[seat].hand.len();
+        hand.saturating_sub(self.hand_limit(seat))
+    }
+
+    /// The discard the current player is asked for, while the game waits for it.
+    fn discard_prompt(&self) -> Option<(&str, Prompt)> {
+        let seat = self.state.turn.current;
+        let count = self.excess_cards(seat);
+        if self.is_over() || self.state.turn.phase != Phase::Discard || count == 0 {
+            return None;
+        }
+        let player = &self.state.players[seat];
+        let hand = player.hand.cards().iter().map(|c| c.id).collect();
+        let prompt = Prompt::Discard {
+            count: count as u32,
+            hand,
+        };
+        Some((&player.name, prompt))
+    }
+
+    /// Every card the player has, in hand, in front of the player and in the judgment zone,
+    /// with where it is.
+    fn cards_of(&self, seat: Seat) -> Vec<(CardId, Zone)> {
+        let player = &self.state.players[seat];
+        let hand = player.hand.cards().iter().map(|c| (c.id, Zone::Hand(seat)));
+        let equipment = player
+            .equipment
+            .cards()
+            .map(|c| (c.id, Zone::Equipment(seat)));
+        let judgment = player
+            .judgment
+            .cards()
+            .iter()
+            .map(|c| (c.id, Zone::Judgment(seat)));
+        hand.chain(equipment).chain(judgment).collect()
+    }
+
+    /// Throw away a card of the target, picked at random.
+    pub(super) fn dismantle(&mut self, target: Seat) {
+        let cards = self.cards_of(target);
+        if cards.is_empty() {
+            return;
+        }
+        let player = self.state.players[target].name.clone();
+        match cards[self.state.deck.pick(cards.len())] {
+            (card, Zone::Equipment(_)) => self.emit(GameEvent::Unequipped { player, card }),
+            (card, Zone::Judgment(_)) => self.emit(GameEvent::Cancelled {
+                card,
+                target: player,
+            }),
+            (card, _) => self.emit(GameEvent::CardsDiscarded {
+                player,
+                cards: vec![card],
+            }),
+        }
+    }
+
+    /// Take a card of the target, picked at random, into the hand of the player.
+    pub(super) fn snatch(&mut self, seat: Seat, target: Seat) {
+        let cards = self.cards_of(target);
+        if cards.is_empty() {
+            return;
+        }
+        let player = self.state.players[seat].name.clone();
+        let from = self.state.players[target].name.clone();
+        match cards[self.state.deck.pick(cards.len())] {
+            (card, Zone::Hand(_)) => self.emit(GameEvent::CardGiven {
+                player: from,
+                to: player,
+                card: Some(card),
+            }),
+            (card, _) => self.emit(GameEvent::CardTaken { player, from, card }),
+        }
+    }
+
+    /// Draw from the top of the draw pile. The discard pile is shuffled into it whenever
+    /// it runs out.
+    fn draw(&mut self, seat: Seat, count: usize) {
+        let mut left = count;
+        loop {
+            self.reshuffle_if_empty();
+            let cards: Vec<CardId> = self
+                .state
+                .deck
+                .draw_pile
+                .cards()
+                .iter()
+                .rev()
+                .take(left)
+                .map(|c| c.id)
+                .collect();
+            left -= cards.len();
+            self.emit(GameEvent::CardsDrawn {
+                player: self.state.players[seat].name.clone(),
+                count: cards.len() as u32,
+                cards,
+            });
+            if left == 0 || !self.state.deck.needs_reshuffle() {
+                break;
+            }
+        }
+    }
+
+    /// The top card of the draw pile, after shuffling the discard pile into it when it ran
+    /// out.
+    fn top_card(&mut self) -> Option<Card> {
+        self.reshuffle_if_empty();
+        self.state.deck.draw_pile.cards().last().cloned()
+    }
+
+    fn reshuffle_if_empty(&mut self) {
+        if !self.state.deck.needs_reshuffle() {
+            return;
+        }
+        let mut order: Vec<CardId> = self
+            .state
+            .deck
+            .discard_pile
+            .cards()
+            .iter()
+            .map(|c| c.id)
+            .collect();
+        self.state.deck.shuffle(&mut order);
+        self.emit(GameEvent::DeckReshuffled {
+            cards: order.len() as u32,
+            order,
+        });
+    }
+
+    fn next_phase(&mut self) {
+        self.advance_phase();
+        self.run_automatic_phases();
+    }
+
+    fn advance_phase(&mut self) {
+        let mut turn = self.state.turn;
+        turn.advance(&self.state.alive());
+        self.phase_done = false;
+        if turn.phase == Phase::Start {
+            self.skills_used.clear();
+            self.attacked = false;
+            self.skipped.clear();
+        }
+        self.enter_phase(turn);
+    }
+
+    /// Run the phases that need no decision, until a phase waits for the current player or
+    /// somebody is asked to respond. Phases a judgment took away are passed over.
+    fn run_automatic_phases(&mut self) {
+        while self.window.is_none() && !self.is_over() {
+            let seat = self.state.turn.current;
+            let skipped = self.skipped.contains(&self.state.turn.phase);
+            match self.state.turn.phase {
+                Phase::Play if skipped => {}
+                Phase::Play => return,
+                Phase::Discard => match self.excess_cards(seat) {
+                    0 => {}
+                    count => {
+                        if !self.phase_done {
+                            self.phase_done = true;
+                            self.emit(GameEvent::DiscardRequested {
+                                player: self.state.players[seat].name.clone(),
+                                count: count as u32,
+                            });
+                        }
+                        return;
+                    }
+                },
+                _ if self.phase_done || skipped => {}
+                Phase::Judgment => {
+                    // The judgments are effects, they may wait for responses
+                    self.phase_done = true;
+                    self.queue_judgments(seat);
+                    self.proceed();
+                    return;
+                }
+                Phase::Draw => self.draw(seat, self.draw_phase_cards(seat)),
+                Phase::Start | Phase::End => {}
+            }
+            self.phase_done = true;
+            if self.window.is_none() && !self.is_over() {
+                self.advance_phase();
+            }
+        }
+    }
+
+    fn enter_phase(&mut self, turn: TurnState) {
+        let player = self.state.players[turn.current].name.clone();
+        if turn.phase == Phase::Start {
+            self.emit(GameEvent::TurnStarted {
+                player: player.clone(),
+                round: turn.round,
+            });
+        }
+        self.emit(GameEvent::PhaseChanged {
+            player,
+            phase: turn.phase,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The cards and characters of the tests. All copies of a kind have the same suit and
+    /// rank, so it is known how a judgment turns out with the card flipped.
+    const CARDS: &str = r#"(
+        cards: [
+            (name: "Attack", category: Basic, effects: ["damage"], target: OtherInAttackRange,
+                art: "", copies: [(Spade, 7), (Spade, 7), (Spade, 7), (Spade, 7), (Spade, 7),
+                (Spade, 7), (Spade, 7), (Spade, 7), (Spade, 7), (Spade, 7), (Spade, 7),
+                (Spade, 7)]),
+            (name: "Dodge", category: Basic, effects: ["cancel_attack"], target: Response,
+                art: "", copies: [(Diamond, 2), (Diamond, 2), (Diamond, 2), (Diamond, 2),
+                (Diamond, 2), (Diamond, 2)]),
+            (name: "Peach", category: Basic, effects: ["heal"], target: Myself, art: "",
+                copies: [(Heart, 3), (Heart, 3), (Heart, 3), (Heart, 3), (Heart, 3),
+                (Heart, 3)]),
+            (name: "Duel", category: Trick, effects: ["duel"], target: Other, art: "",
+                copies: [(Club, 1), (Club, 1)]),
+            (name: "Dismantle", category: Trick, effects: ["discard_one"], target: Other,
+                art: "", copies: [(Club, 3), (Club, 3)]),
+            (name: "Snatch", category: Trick, effects: ["steal_one"], target: Other,
+                range: Some(1), art: "", copies: [(Diamond, 4), (Diamond, 4)]),
+            (name: "Nullify", category: Trick, effects: ["cancel_trick"], target: Response,
+                art: "", copies: [(Club, 12), (Club, 12), (Club, 12), (Club, 12)]),
+            (name: "Harvest", category: Trick, effects: ["harvest"], target: Everyone,
+                art: "", copies: [(Heart, 4)]),
+            (name: "Lightning", category: DelayedTrick, effects: ["lightning"],
+                target: Myself, art: "", copies: [(Spade, 1)]),
+            (name: "Indulgence", category: DelayedTrick, effects: ["skip_play_phase"],
+                target: Other, art: "", copies: [(Heart, 6)]),
+            (name: "Crossbow", category: Equipment(Weapon), effects: ["unlimited_attacks"],
+                target: Myself, range: Some(1), art: "", copies: [(Club, 1)]),
+        ],
+        characters: [
+            (name: "Flandre", health: 3, skills: [
+                (name: "Destruction", kind: Passive, effect: "heavy_attack",
+                    description: ""),
+                (name: "Four of a Kind", kind: Passive, effect: "hand_limit_plus_two",
+                    description: ""),
+            ]),
+            (name: "Kanako", health: 4, skills: [
+                (name: "Divine Faith", kind: Passive, effect: "draw_extra", description: ""),
+                (name: "Divine Oracle", kind: Triggered(Judgment), effect: "rejudge",
+                    description: ""),
+            ]),
+            (name: "Kokoro", health: 3, skills: [
+                (name: "Mask of Hope", kind: Passive, effect: "attack_as_dodge",
+                    description: ""),
+                (name: "Mask of Anger", kind: Triggered(Damaged), effect: "draw_on_damage",
+                    description: ""),
+            ]),
+            (name: "Alice", health: 3, skills: [
+                (name: "Puppeteer", kind: Active, effect: "give_card_draw", description: ""),
+            ]),
+            (name: "Cirno", health: 4, skills: [
+                (name: "Perfect Freeze", kind: Triggered(DealtDamage), effect: "freeze",
+                    description: ""),
+            ]),
+        ],
+    )"#;
+
+    /// Seats the players of the tests in this order.
+    pub(super) const PLAYERS: [&str; 4] = ["alice", "bob", "carol", "dave"];
+
+    /// A game of everybody for themselves, not started yet, with a player for every given
+    /// character. The named cards are put on top of the draw pile, the first one is drawn
+    /// first: the opening hands are dealt from them in seat order, then the first player
+    /// draws.
+    pub(super) fn setup(characters: &[Option<&str>], top: &[&str]) -> Engine {
+        let database = CardDatabase::parse(CARDS).unwrap();
+        let players = PLAYERS
+            .iter()
+            .zip(characters)
+            .map(|(name, character)| (name.to_string(), character.map(String::from)))
+            .collect();
+        let mut engine = Engine::new(database, players, GameMode::FreeForAll, 7);
+        let pile = &mut engine.state.deck.draw_pile;
+        let mut stacked = Vec::with_capacity(top.len());
+        for name in top {
+            let id = pile.cards().iter().find(|c| c.name == *name).map(|c| c.id);
+            let id = id.unwrap_or_else(|| panic!("No {} left to stack", name));
+            stacked.extend(pile.take(id));
+        }
+        for card in stacked.into_iter().rev() {
+            pile.push(card);
+        }
+        engine
+    }
+
+    /// The game of `setup`, started.
+    pub(super) fn game(characters: &[Option<&str>], top: &[&str]) -> Engine {
+        let mut engine = setup(characters, top);
+        engine.start();
+        engine
+    }
+
+    /// A card of the given name in the hand of the player.
+    pub(super) fn card(engine: &Engine, seat: Seat, name: &str) -> CardId {
+        let hand = engine.state().players[seat].hand.cards();
+        match hand.iter().find(|c| c.name == name) {
+            Some(card) => card.id,
+            None => panic!("{} has no {}", PLAYERS[seat], name),
+        }
+    }
+
+    pub(super) fn hand(engine: &Engine, seat: Seat) -> Vec<CardId> {
+        let hand = engine.state().players[seat].hand.cards();
+        hand.iter().map(|c| c.id).collect()
+    }
+
+    /// Put the trick of the given name in front of the player, before the game starts.
+    pub(super) fn lay(engine: &mut Engine, seat: Seat, name: &str) {
+        let deck = &mut engine.state.deck.draw_pile;
+        let id = deck.cards().iter().find(|c| c.name == name).map(|c| c.id);
+        let trick = id.and_then(|id| deck.take(id)).expect("No such trick left");
+        engine.state.players[seat].judgment.push(trick);
+    }
+
+    /// Let the player who is asked answer with the card, or pass.
+    pub(super) fn respond(engine: &mut Engine, card: Option<CardId>) -> Vec<GameEvent> {
+        let asked = engine.asked().expect("Nobody is asked");
+        let name = engine.state().players[asked].name.clone();
+        engine.act(&name, GameAction::Respond { card }).unwrap()
+    }
+
+    /// Let everybody asked pass, until the game goes on.
+    pub(super) fn pass_all(engine: &mut Engine) -> Vec<GameEvent> {
+        let mut events = vec![];
+        while engine.asked().is_some() {
+            events.extend(respond(engine, None));
+        }
+        events
+    }
+
+    pub(super) fn play(card: CardId, targets: &[&str]) -> GameAction {
+        GameAction::PlayCard {
+            card,
+            targets: targets.iter().map(|t| t.to_string()).collect(),
+        }
+    }
+
+    fn phases(events: &[GameEvent]) -> Vec<Phase> {
+        events
+            .iter()
+            .filter_map(|event| match event {
+                GameEvent::PhaseChanged { phase, .. } => Some(*phase),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn the_first_turn_runs_up_to_the_play_phase() {
+        let engine = game(&[None, None], &[]);
+        let phases = phases(engine.log());
+        assert_eq!(
+            phases,
+            vec![Phase::Start, Phase::Judgment, Phase::Draw, Phase::Play]
+        );
+        let turn = TurnState {
+            round: 1,
+            current: 0,
+            phase: Phase::Play,
+        };
+        assert_eq!(engine.state().turn, turn);
+        assert_eq!(hand(&engine, 0).len(), OPENING_HAND + DRAW_PHASE_CARDS);
+        assert_eq!(hand(&engine, 1).len(), OPENING_HAND);
+    }
+
+    #[test]
+    fn turns_go_around_the_table() {
+        let mut engine = game(&[None, None], &[]);
+        engine.act("alice", GameAction::EndPhase).unwrap();
+        assert_eq!(engine.state().turn.phase, Phase::Discard);
+        let cards = hand(&engine, 0)[..DRAW_PHASE_CARDS].to_vec();
+        let events = engine.act("alice", GameAction::Discard { cards }).unwrap();
+        assert_eq!(
+            phases(&events),
+            vec![
+                Phase::End,
+                Phase::Start,
+                Phase::Judgment,
+                Phase::Draw,
+                Phase::Play
+            ]
+        );
+        let started = GameEvent::TurnStarted {
+            player: "bob".to_string(),
+            round: 1,
+        };
+        assert!(events.contains(&started));
+        assert_eq!(engine.state().turn.current, 1);
+
+        // Nobody acting in time ends the play phase, then discards for the player
+        engine.time_out();
+        engine.time_out();
+        let turn = TurnState {
+            round: 2,
+            current: 0,
+            phase: Phase::Play,
+        };
+        assert_eq!(engine.state().turn, turn);
+    }
+
+    #[test]
+    fn illegal_actions_change_nothing() {
+        let top = [
+            "Attack", "Dodge", "Peach", "Attack", "Attack", "Attack", "Attack", "Attack",
+        ];
+        let mut engine = game(&[None, None, None, None], &top);
+        let before = engine.state().clone();
+        let logged = engine.log().len();
+        let attack = card(&engine, 0, "Attack");
+        let dodge = card(&engine, 0, "Dodge");
+        let peach = card(&engine, 0, "Peach");
+        let theirs = card(&engine, 1, "Attack");
+
+        let refused = vec![
+            ("bob", play(theirs, &["alice"]), ActionError::NotYourTurn),
+            ("bob", GameAction::EndPhase, ActionError::NotYourTurn),
+            ("zed", GameAction::EndPhase, ActionError::NotPlaying),
+            (
+                "alice",
+                GameAction::Discard { cards: vec![dodge] },
+                ActionError::WrongPhase(Phase::Play),
+            ),
+            (
+                "alice",
+                GameAction::Respond { card: None },
+                ActionError::Unexpected,
+            ),
+            (
+                "alice",
+                play(theirs, &["bob"]),
+                ActionError::NotInHand(theirs),
+            ),
+            ("alice", play(dodge, &[]), ActionError::NotPlayable(dodge)),
+            // Nobody heals above the maximum
+            ("alice", play(peach, &[]), ActionError::NotPlayable(peach)),
+            ("alice", play(attack, &[]), ActionError::InvalidTargets),
+            (
+                "alice",
+                play(attack, &["alice"]),
+                ActionError::InvalidTargets,
+            ),
+            (
+                "alice",
+                play(attack, &["zed"]),
+                ActionError::UnknownTarget("zed".to_string()),
+            ),
+            (
+                "alice",
+                play(attack, &["carol"]),
+                ActionError::OutOfRange("carol".to_string()),
+            ),
+        ];
+        for (player, action, error) in refused {
+            assert_eq!(
+                engine.act(player, action.clone()),
+                Err(error),
+                "{:?}",
+                action
+            );
+        }
+        assert_eq!(engine.state(), &before);
+        assert_eq!(engine.log().len(), logged);
+    }
+
+    #[test]
+    fn one_attack_per_turn_without_a_crossbow() {
+        let top = [
+            "Attack", "Attack", "Attack", "Crossbow", "Peach", "Peach", "Peach", "Peach",
+        ];
+        let mut engine = game(&[None, None], &top);
+        let attacks: Vec<CardId> = hand(&engine, 0)[..3].to_vec();
+        engine.act("alice", play(attacks[0], &["bob"])).unwrap();
+        pass_all(&mut engine);
+        assert_eq!(engine.state().players[1].health, 3);
+        assert_eq!(
+            engine.act("alice", play(attacks[1], &["bob"])),
+            Err(ActionError::NotPlayable(attacks[1]))
+        );
+
+        let crossbow = card(&engine, 0, "Crossbow");
+        engine.act("alice", play(crossbow, &[])).unwrap();
+        engine.act("alice", play(attacks[1], &["bob"])).unwrap();
+        pass_all(&mut engine);
+        engine.act("alice", play(attacks[2], &["bob"])).unwrap();
+        pass_all(&mut engine);
+        assert_eq!(engine.state().players[1].health, 1);
+    }
+
+    #[test]
+    fn a_duel_is_lost_by_who_stops_attacking() {
+        let top = [
+            "Duel", "Attack", "Peach", "Peach", "Attack", "Peach", "Peach", "Peach",
+        ];
+        let mut engine = game(&[None, None], &top);
+        let duel = card(&engine, 0, "Duel");
+        engine.act("alice", play(duel, &["bob"])).unwrap();
+        // Nobody nullifies the duel
+        respond(&mut engine, None);
+        respond(&mut engine, None);
+
+        let attack = card(&engine, 1, "Attack");
+        respond(&mut engine, Some(attack));
+        let attack = card(&engine, 0, "Attack");
+        respond(&mut engine, Some(attack));
+        let events = respond(&mut engine, None);
+        let damaged = GameEvent::Damaged {
+            player: "bob".to_string(),
+            source: Some("alice".to_string()),
+            card: Some(duel),
+            amount: 1,
+            health: 3,
+        };
+        assert!(events.contains(&damaged));
+        assert_eq!(engine.asked(), None);
+        assert_eq!(engine.state().players[0].health, 4);
+    }
+
+    #[test]
+    fn dismantle_throws_away_a_card_of_the_target() {
+        let top = ["Dismantle", "Dismantle"];
+        let mut engine = game(&[None, None], &top);
+        let dismantle = card(&engine, 0, "Dismantle");
+        engine.act("alice", play(dismantle, &["bob"])).unwrap();
+        let events = pass_all(&mut engine);
+        let cards = match events.last() {
+            Some(GameEvent::CardsDiscarded { player, cards }) if player == "bob" => cards,
+            last => panic!("Nothing of bob was thrown away, but {:?}", last),
+        };
+        assert_eq!(hand(&engine, 1).len(), OPENING_HAND - 1);
+        assert!(engine.state().deck.discard_pile.contains(cards[0]));
+
+        // Nothing to throw away, nothing to play it on
+        engine.state.players[1].hand.take_all();
+        let dismantle = card(&engine, 0, "Dismantle");
+        assert_eq!(
+            engine.act("alice", play(dismantle, &["bob"])),
+            Err(ActionError::InvalidTargets)
+        );
+    }
+
+    #[test]
+    fn snatch_takes_a_card_into_the_hand() {
+        let mut engine = game(&[None, None], &["Snatch"]);
+        let snatch = card(&engine, 0, "Snatch");
+        let theirs = hand(&engine, 1);
+        engine.act("alice", play(snatch, &["bob"])).unwrap();
+        let events = pass_all(&mut engine);
+        let taken = match events.last() {
+            Some(GameEvent::CardGiven {
+                player,
+                to,
+                card: Some(card),
+            }) if player == "bob" && to == "alice" => *card,
+            last => panic!("Nothing of bob was taken, but {:?}", last),
+        };
+        assert!(theirs.contains(&taken));
+        assert!(hand(&engine, 0).contains(&taken));
+        assert_eq!(hand(&engine, 1).len(), OPENING_HAND - 1);
+    }
+
+    /// A game with hidden roles, not started yet, in which the players have the given roles
+    /// in seat order. The first player takes the first turn.
+    fn with_roles(roles: &[Role], top: &[&str]) -> Engine {
+        let mut engine = setup(&vec![None; roles.len()], top);
+        engine.mode = GameMode::Roles;
+        for (player, role) in engine.state.players.iter_mut().zip(roles) {
+            player.role = Some(*role);
+        }
+        engine
+    }
+
+    /// Let the first player kill the one at the given seat, who has one health left, with
+    /// an Attack.
+    fn kill(mut engine: Engine, seat: Seat) -> Vec<GameEvent> {
+        engine.state.players[seat].health = 1;
+        engine.start();
+        let attack = card(&engine, 0, "Attack");
+        engine.act("alice", play(attack, &[PLAYERS[seat]])).unwrap();
+        let events = pass_all(&mut engine);
+        assert!(engine.is_over());
+        assert_eq!(
+            engine.act("alice", GameAction::EndPhase),
+            Err(ActionError::GameOver)
+        );
+        events
+    }
+
+    fn winners(events: &[GameEvent]) -> Option<Vec<&str>> {
+        events.iter().find_map(|event| match event {
+            GameEvent::GameOver { winners, .. } => {
+                Some(winners.iter().map(String::as_str).collect())
+            }
+            _ => None,
+        })
+    }
+
+    #[test]
+    fn the_rebels_win_once_the_leader_is_dead() {
+        let roles = [Role::Rebel, Role::Leader, Role::Renegade];
+        let events = kill(with_roles(&roles, &["Attack"]), 1);
+        assert_eq!(winners(&events), Some(vec!["alice"]));
+        // Everybody's role is shown
+        let roles = events.iter().find_map(|event| match event {
+            GameEvent::GameOver { roles, .. } => Some(roles.clone()),
+            _ => None,
+        });
+        let carol = ("carol".to_string(), Some(Role::Renegade));
+        assert!(roles.unwrap().contains(&carol));
+    }
+
+    #[test]
+    fn the_leader_wins_with_the_loyalists() {
+        let roles = [Role::Leader, Role::Loyalist, Role::Rebel];
+        let events = kill(with_roles(&roles, &["Attack"]), 2);
+        assert_eq!(winners(&events), Some(vec!["alice", "bob"]));
+    }
+
+    #[test]
+    fn the_renegade_wins_as_the_last_one_left() {
+        let roles = [Role::Renegade, Role::Leader];
+        let events = kill(with_roles(&roles, &["Attack"]), 1);
+        assert_eq!(winners(&events), Some(vec!["alice"]));
+    }
+
+    #[test]
+    fn the_last_one_alive_wins_without_roles() {
+        let events = kill(setup(&[None, None], &["Attack"]), 1);
+        assert_eq!(winners(&events), Some(vec!["alice"]));
+    }
+
+    #[test]
+    fn the_game_goes_on_while_nobody_won() {
+        let roles = [Role::Leader, Role::Rebel, Role::Renegade];
+        let mut engine = with_roles(&roles, &["Attack"]);
+        engine.state.players[1].health = 1;
+        engine.start();
+        let attack = card(&engine, 0, "Attack");
+        engine.act("alice", play(attack, &["bob"])).unwrap();
+        let events = pass_all(&mut engine);
+        assert_eq!(winners(&events), None);
+        assert!(!engine.is_over());
+    }
+
+    #[test]
+    fn the_discard_takes_exactly_the_cards_over_the_limit() {
+        let mut engine = game(&[None, None], &[]);
+        engine.act("alice", GameAction::EndPhase).unwrap();
+        let cards = hand(&engine, 0);
+        let prompt = Prompt::Discard {
+            count: 2,
+            hand: cards.clone(),
+        };
+        assert_eq!(engine.prompt(), Some(("alice", prompt)));
+        assert_eq!(
+            engine.act("alice", GameAction::EndPhase),
+            Err(ActionError::MustDiscard(2))
+        );
+
+        let theirs = hand(&engine, 1)[0];
+        let refused = vec![
+            (vec![cards[0]], ActionError::MustDiscard(2)),
+            (vec![cards[0], cards[0]], ActionError::MustDiscard(2)),
+            (cards[..3].to_vec(), ActionError::MustDiscard(2)),
+            (vec![cards[0], theirs], ActionError::NotInHand(theirs)),
+        ];
+        for (cards, error) in refused {
+            let discard = GameAction::Discard { cards };
+            assert_eq!(engine.act("alice", discard), Err(error));
+        }
+
+        // A card named twice counts once
+        let discard = GameAction::Discard {
+            cards: vec![cards[0], cards[1], cards[0]],
+        };
+        let events = engine.act("alice", discard).unwrap();
+        let discarded = GameEvent::CardsDiscarded {
+            player: "alice".to_string(),
+            cards: cards[..2].to_vec(),
+        };
+        assert_eq!(events.first(), Some(&discarded));
+        assert_eq!(engine.state().turn.current, 1);
+    }
+
+    #[test]
+    fn the_cards_drawn_last_are_discarded_in_time() {
+        let mut engine = game(&[None, None], &[]);
+        engine.time_out();
+        let cards = hand(&engine, 0);
+        let events = engine.time_out();
+        let discarded = GameEvent::CardsDiscarded {
+            player: "alice".to_string(),
+            cards: cards[OPENING_HAND..].to_vec(),
+        };
+        assert_eq!(events.first(), Some(&discarded));
+        assert_eq!(hand(&engine, 0), cards[..OPENING_HAND].to_vec());
+    }
+
+    #[test]
+    fn nothing_is_discarded_within_the_limit() {
+        let mut engine = game(&[None, None], &["Attack", "Crossbow"]);
+        let crossbow = card(&engine, 0, "Crossbow");
+        engine.act("alice", play(crossbow, &[])).unwrap();
+        let attack = card(&engine, 0, "Attack");
+        engine.act("alice", play(attack, &["bob"])).unwrap();
+        pass_all(&mut engine);
+        assert_eq!(hand(&engine, 0).len(), OPENING_HAND);
+
+        let events = engine.act("alice", GameAction::EndPhase).unwrap();
+        assert!(!events
+            .iter()
+            .any(|event| matches!(event, GameEvent::DiscardRequested { .. })));
+        assert_eq!(engine.state().turn.current, 1);
+    }
+
+    /// Take the first action the engine allows: answer with the first card that fits, play
+    /// the first card that can be played, or let the time run out.
+    fn step(engine: &mut Engine) {
+        let seat = engine.asked().unwrap_or(engine.state().turn.current);
+        let name = PLAYERS[seat];
+        let mut actions = vec![];
+        for card in hand(engine, seat) {
+            if engine.asked().is_some() {
+                actions.push(GameAction::Respond { card: Some(card) });
+                continue;
+            }
+            actions.push(play(card, &[]));
+            let players = engine.state().players.len();
+            actions.extend(PLAYERS[..players].iter().map(|to| play(card, &[to])));
+        }
+        if actions.into_iter().all(|a| engine.act(name, a).is_err()) {
+            engine.time_out();
+        }
+    }
+
+    #[test]
+    fn the_log_replays_the_game() {
+        let characters = [Some("Kanako"), Some("Cirno"), Some("Kokoro"), None];
+        let mut engine = setup(&characters, &[]);
+        let mut replayed = engine.state().clone();
+        engine.start();
+        for _ in 0..500 {
+            if engine.is_over() {
+                break;
+            }
+            step(&mut engine);
+        }
+        let log = engine.log();
+        assert!(log
+            .iter()
+            .any(|event| matches!(event, GameEvent::DeckReshuffled { .. })));
+        for event in log {
+            replayed.apply(event, engine.database());
+        }
+
+        // The random number generator of the deck is not part of the log
+        let state = engine.state();
+        assert_eq!(replayed.players, state.players);
+        assert_eq!(replayed.deck.draw_pile, state.deck.draw_pile);
+        assert_eq!(replayed.deck.discard_pile, state.deck.discard_pile);
+        assert_eq!(replayed.turn, state.turn);
+    }
+}
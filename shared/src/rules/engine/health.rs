@@ -0,0 +1,211 @@
+use super::{skills::Occasion, window::Reason, Engine};
+use crate::rules::{card::CardId, character::Trigger, event::GameEvent, player::Seat, turn::Phase};
+
+impl Engine {
+    /// Take health from a player, as much as the skills of the source make it. A player
+    /// without health left starts dying.
+    pub(super) fn damage(
+        &mut self,
+        target: Seat,
+        source: Option<Seat>,
+        card: Option<CardId>,
+        amount: u32,
+    ) {
+        let amount = self.damage_amount(source, card, amount);
+        let player = &self.state.players[target];
+        self.emit(GameEvent::Damaged {
+            player: player.name.clone(),
+            source: source.map(|s| self.state.players[s].name.clone()),
+            card,
+            amount,
+            health: player.health - amount as i32,
+        });
+        let taken = Occasion {
+            other: source,
+            card,
+            amount,
+        };
+        self.trigger(target, Trigger::Damaged, taken);
+        if let Some(source) = source {
+            let dealt = Occasion {
+                other: Some(target),
+                ..taken
+            };
+            self.trigger(source, Trigger::DealtDamage, dealt);
+        }
+        if self.state.players[target].is_dying() {
+            self.start_rescue(target, source);
+        }
+    }
+
+    /// Give health back to a player, but not above the maximum.
+    pub(super) fn heal(&mut self, target: Seat, source: Option<Seat>, amount: u32) {
+        let player = &self.state.players[target];
+        let healed = (player.max_health - player.health)
+            .min(amount as i32)
+            .max(0);
+        if healed == 0 {
+            return;
+        }
+        self.emit(GameEvent::Healed {
+            player: player.name.clone(),
+            source: source.map(|s| self.state.players[s].name.clone()),
+            amount: healed as u32,
+            health: player.health + healed,
+        });
+    }
+
+    /// Ask everybody in seat order, starting with the player whose turn it is, to save
+    /// the dying player.
+    fn start_rescue(&mut self, dying: Seat, source: Option<Seat>) {
+        let player = &self.state.players[dying];
+        self.emit(GameEvent::Dying {
+            player: player.name.clone(),
+            health: player.health,
+        });
+        let asking = self.seat_order(self.state.turn.current).collect();
+        self.open_window(Reason::Rescue { dying, source }, asking);
+    }
+
+    /// Nobody saved the player. Everything the player had is shown and discarded, and the
+    /// player leaves the turn order. A player who dies in the own turn ends it.
+    pub(super) fn die(&mut self, seat: Seat, killer: Option<Seat>) {
+        let player = &self.state.players[seat];
+        let revealed = player
+            .hand
+            .cards()
+            .iter()
+            .chain(player.judgment.cards())
+            .chain(player.equipment.cards())
+            .map(|c| c.id)
+            .collect();
+        self.emit(GameEvent::PlayerDied {
+            player: player.name.clone(),
+            killer: killer.map(|k| self.state.players[k].name.clone()),
+            role: player.role,
+            revealed,
+        });
+        self.check_victory();
+        if seat == self.state.turn.current && !self.is_over() {
+            self.emit(GameEvent::PhaseChanged {
+                player: self.state.players[seat].name.clone(),
+                phase: Phase::End,
+            });
+            self.phase_done = true;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{
+        tests::{card, game, hand, pass_all, play, respond, setup},
+        ActionError,
+    };
+    use crate::{
+        msg::GameAction,
+        rules::{event::GameEvent, turn::Phase},
+    };
+
+    #[test]
+    fn a_peach_saves_the_dying() {
+        let top = [
+            "Attack", "Peach", "Peach", "Peach", "Peach", "Attack", "Attack", "Attack",
+        ];
+        let mut engine = setup(&[None, None, None], &top);
+        engine.state.players[1].health = 1;
+        engine.start();
+        let attack = card(&engine, 0, "Attack");
+        engine.act("alice", play(attack, &["bob"])).unwrap();
+        let events = respond(&mut engine, None);
+        let dying = GameEvent::Dying {
+            player: "bob".to_string(),
+            health: 0,
+        };
+        assert!(events.contains(&dying));
+
+        // Everybody is asked in seat order, starting with the current player
+        respond(&mut engine, None);
+        let peach = card(&engine, 1, "Peach");
+        let events = respond(&mut engine, Some(peach));
+        let rescued = GameEvent::Rescued {
+            player: "bob".to_string(),
+        };
+        assert!(events.contains(&rescued));
+        assert_eq!(engine.asked(), None);
+        assert!(engine.state().players[1].alive);
+        assert_eq!(engine.state().players[1].health, 1);
+    }
+
+    #[test]
+    fn the_dying_is_asked_until_saved() {
+        let top = [
+            "Attack", "Attack", "Attack", "Attack", "Peach", "Peach", "Peach", "Attack",
+        ];
+        let mut engine = setup(&[Some("Flandre"), None], &top);
+        engine.state.players[1].health = 1;
+        engine.start();
+        let attack = card(&engine, 0, "Attack");
+        engine.act("alice", play(attack, &["bob"])).unwrap();
+        respond(&mut engine, None);
+        assert_eq!(engine.state().players[1].health, -1);
+
+        respond(&mut engine, None);
+        let peach = card(&engine, 1, "Peach");
+        respond(&mut engine, Some(peach));
+        assert_eq!(engine.asked(), Some(1));
+        let peach = card(&engine, 1, "Peach");
+        respond(&mut engine, Some(peach));
+        assert_eq!(engine.asked(), None);
+        assert_eq!(engine.state().players[1].health, 1);
+    }
+
+    #[test]
+    fn nobody_saving_the_dying_kills() {
+        let top = [
+            "Attack", "Peach", "Peach", "Peach", "Attack", "Attack", "Dodge", "Attack",
+        ];
+        let mut engine = setup(&[None, None, None], &top);
+        engine.state.players[1].health = 1;
+        engine.start();
+        let theirs = hand(&engine, 1);
+        let attack = card(&engine, 0, "Attack");
+        engine.act("alice", play(attack, &["bob"])).unwrap();
+        // Not even the Dodge in hand is played
+        let events = pass_all(&mut engine);
+        let died = GameEvent::PlayerDied {
+            player: "bob".to_string(),
+            killer: Some("alice".to_string()),
+            role: None,
+            revealed: theirs.clone(),
+        };
+        assert_eq!(events.last(), Some(&died));
+        assert!(!engine.is_over());
+        let state = engine.state();
+        assert!(!state.players[1].alive);
+        assert!(state.players[1].hand.is_empty());
+        assert!(theirs.iter().all(|c| state.deck.discard_pile.contains(*c)));
+
+        // The dead are passed over
+        engine.act("alice", GameAction::EndPhase).unwrap();
+        let cards = hand(&engine, 0)[..1].to_vec();
+        engine.act("alice", GameAction::Discard { cards }).unwrap();
+        assert_eq!(engine.state().turn.current, 2);
+        assert_eq!(engine.state().turn.phase, Phase::Play);
+    }
+
+    #[test]
+    fn a_dead_player_cannot_act_or_be_targeted() {
+        let mut engine = game(&[None, None, None], &["Attack"]);
+        let attack = card(&engine, 0, "Attack");
+        engine.state.players[1].alive = false;
+        assert_eq!(
+            engine.act("bob", GameAction::EndPhase),
+            Err(ActionError::NotPlaying)
+        );
+        assert_eq!(
+            engine.act("alice", play(attack, &["bob"])),
+            Err(ActionError::UnknownTarget("bob".to_string()))
+        );
+    }
+}
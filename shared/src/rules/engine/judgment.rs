@@ -0,0 +1,255 @@
+use super::{window::Effect, ActionError, Engine};
+use crate::rules::{
+    card::{Card, Suit},
+    event::GameEvent,
+    player::Seat,
+    turn::Phase,
+};
+
+/// Damage a striking lightning deals.
+pub const LIGHTNING_DAMAGE: u32 = 3;
+
+/// The top card of the draw pile, flipped to decide whether a delayed trick takes effect.
+/// Skills may change the flipped card before the outcome is decided.
+#[derive(Debug, Clone)]
+pub(super) struct Judgment {
+    /// Whose judgment phase it is
+    pub seat: Seat,
+    pub trick: Card,
+    pub effect: String,
+    pub flipped: Card,
+}
+
+impl Judgment {
+    /// Whether the trick takes effect with the flipped card.
+    pub fn hits(&self) -> bool {
+        let card = &self.flipped;
+        match self.effect.as_str() {
+            "skip_play_phase" => card.suit != Suit::Heart,
+            "skip_draw_phase" => card.suit != Suit::Club,
+            "lightning" => card.suit == Suit::Spade && (2..=9).contains(&card.rank),
+            _ => false,
+        }
+    }
+}
+
+impl Engine {
+    /// Put a delayed trick in front of the target, where it waits for the judgment phase.
+    /// Nobody can have two of the same trick waiting.
+    pub(super) fn place_trick(
+        &mut self,
+        seat: Seat,
+        card: &Card,
+        target: Seat,
+    ) -> Result<(), ActionError> {
+        let waiting = self.state.players[target].judgment.cards();
+        if waiting.iter().any(|c| c.name == card.name) {
+            return Err(ActionError::NotPlayable(card.id));
+        }
+        self.emit(GameEvent::TrickPlaced {
+            player: self.state.players[seat].name.clone(),
+            card: card.id,
+            target: self.state.players[target].name.clone(),
+        });
+        Ok(())
+    }
+
+    /// Queue the judgments of the delayed tricks in front of the player, the last one
+    /// placed first. Every trick may still be cancelled before its judgment.
+    pub(super) fn queue_judgments(&mut self, seat: Seat) {
+        let tricks: Vec<Card> = self.state.players[seat]
+            .judgment
+            .cards()
+            .iter()
+            .rev()
+            .cloned()
+            .collect();
+        for trick in tricks {
+            let effects = match self.database.definition(&trick) {
+                Some(definition) => definition.effects.clone(),
+                None => continue,
+            };
+            for effect in effects {
+                self.effects
+                    .push_back(Effect::new(seat, &trick, seat, &effect));
+            }
+        }
+    }
+
+    /// Flip the judgment for a delayed trick that was not cancelled and carry out the
+    /// outcome.
+    pub(super) fn judge(&mut self, effect: Effect) {
+        let Effect {
+            target: seat,
+            card: trick,
+            effect,
+            ..
+        } = effect;
+        if !self.state.players[seat].judgment.contains(trick.id) {
+            return;
+        }
+        let flipped = match self.flip(seat, &trick) {
+            Some(flipped) => flipped,
+            // Without cards left nothing can be judged, the trick waits for the next turn
+            None => return,
+        };
+        let mut judgment = Judgment {
+            seat,
+            trick,
+            effect,
+            flipped,
+        };
+        self.modify_judgment(&mut judgment);
+        let hit = judgment.hits();
+        self.emit(GameEvent::Judged {
+            player: self.state.players[seat].name.clone(),
+            trick: judgment.trick.id,
+            card: judgment.flipped.id,
+            hit,
+        });
+
+        let Judgment { trick, effect, .. } = judgment;
+        match effect.as_str() {
+            "skip_play_phase" if hit => self.skipped.push(Phase::Play),
+            "skip_draw_phase" if hit => self.skipped.push(Phase::Draw),
+            "lightning" if hit => self.damage(seat, None, Some(trick.id), LIGHTNING_DAMAGE),
+            "lightning" => self.pass_trick(seat, &trick),
+            _ => {}
+        }
+    }
+
+    /// Reveal the top card of the draw pile as the judgment for the trick. The card goes
+    /// to the discard pile right away.
+    pub(super) fn flip(&mut self, seat: Seat, trick: &Card) -> Option<Card> {
+        let card = self.top_card()?;
+        self.emit(GameEvent::JudgmentFlipped {
+            player: self.state.players[seat].name.clone(),
+            trick: trick.id,
+            card: card.id,
+        });
+        Some(card)
+    }
+
+    /// A lightning that was cancelled or did not strike moves on to the next player who has
+    /// none. It stays with the player if nobody else can take it.
+    pub(super) fn pass_trick(&mut self, seat: Seat, trick: &Card) {
+        let to = self
+            .seat_order(seat)
+            .skip(1)
+            .find(|s| {
+                let waiting = self.state.players[*s].judgment.cards();
+                !waiting.iter().any(|c| c.name == trick.name)
+            })
+            .unwrap_or(seat);
+        self.emit(GameEvent::TrickPassed {
+            card: trick.id,
+            from: self.state.players[seat].name.clone(),
+            to: self.state.players[to].name.clone(),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::{card, game, hand, lay, play, respond, setup};
+    use super::*;
+    use crate::msg::GameAction;
+
+    /// The opening hands of two players, of cards the judgments do not care about.
+    const HANDS: [&str; 8] = [
+        "Dodge", "Dodge", "Dodge", "Dodge", "Dodge", "Dodge", "Peach", "Peach",
+    ];
+
+    /// Start a game of two in which a lightning waits for the first player, and the card
+    /// of the given name is flipped for it.
+    fn lightning(flipped: &str) -> (Engine, Vec<GameEvent>) {
+        let mut top = HANDS.to_vec();
+        top.push(flipped);
+        let mut engine = setup(&[None, None], &top);
+        lay(&mut engine, 0, "Lightning");
+        engine.start();
+        // Nobody nullifies the lightning
+        respond(&mut engine, None);
+        let events = respond(&mut engine, None);
+        (engine, events)
+    }
+
+    #[test]
+    fn the_lightning_strikes_with_a_low_spade() {
+        let (engine, events) = lightning("Attack");
+        assert!(events
+            .iter()
+            .any(|event| matches!(event, GameEvent::Judged { hit: true, .. })));
+        let health = 4 - LIGHTNING_DAMAGE as i32;
+        assert_eq!(engine.state().players[0].health, health);
+        assert!(engine.state().players[0].judgment.is_empty());
+        assert!(engine.state().players[1].judgment.is_empty());
+    }
+
+    #[test]
+    fn the_lightning_moves_on_when_it_misses() {
+        let (engine, events) = lightning("Peach");
+        let lightning = engine.database().deck();
+        let lightning = lightning.iter().find(|c| c.name == "Lightning").unwrap();
+        let passed = GameEvent::TrickPassed {
+            card: lightning.id,
+            from: "alice".to_string(),
+            to: "bob".to_string(),
+        };
+        assert!(events.contains(&passed));
+        assert_eq!(engine.state().players[0].health, 4);
+        assert!(engine.state().players[1].judgment.contains(lightning.id));
+    }
+
+    #[test]
+    fn a_trick_is_placed_in_front_of_the_target_once() {
+        let top = [
+            "Indulgence",
+            "Lightning",
+            "Dodge",
+            "Dodge",
+            "Dodge",
+            "Dodge",
+            "Dodge",
+            "Dodge",
+        ];
+        let mut engine = game(&[None, None], &top);
+        let indulgence = card(&engine, 0, "Indulgence");
+        assert_eq!(
+            engine.act("alice", play(indulgence, &["alice"])),
+            Err(ActionError::InvalidTargets)
+        );
+        let events = engine.act("alice", play(indulgence, &["bob"])).unwrap();
+        let placed = GameEvent::TrickPlaced {
+            player: "alice".to_string(),
+            card: indulgence,
+            target: "bob".to_string(),
+        };
+        assert_eq!(events, vec![placed]);
+        assert!(engine.state().players[1].judgment.contains(indulgence));
+
+        let lightning = card(&engine, 0, "Lightning");
+        engine.act("alice", play(lightning, &[])).unwrap();
+        assert!(engine.state().players[0].judgment.contains(lightning));
+    }
+
+    #[test]
+    fn indulgence_takes_away_the_play_phase() {
+        let mut top = HANDS.to_vec();
+        top.push("Attack");
+        let mut engine = setup(&[None, None], &top);
+        lay(&mut engine, 0, "Indulgence");
+        engine.start();
+        respond(&mut engine, None);
+        respond(&mut engine, None);
+        // The turn went on to the discard phase, the player holds too many cards
+        assert_eq!(engine.state().turn.phase, Phase::Discard);
+        assert_eq!(
+            engine.act("alice", play(hand(&engine, 0)[0], &[])),
+            Err(ActionError::WrongPhase(Phase::Discard))
+        );
+        let cards = hand(&engine, 0)[..2].to_vec();
+        engine.act("alice", GameAction::Discard { cards }).unwrap();
+        assert_eq!(engine.state().turn.current, 1);
+    }
+}
@@ -0,0 +1,367 @@
+use super::{judgment::Judgment, ActionError, Engine, DRAW_PHASE_CARDS};
+use crate::rules::{
+    card::{Card, CardId, Category},
+    character::{SkillKind, Trigger},
+    event::GameEvent,
+    player::Seat,
+};
+
+/// What happened when a skill triggers.
+#[derive(Debug, Clone, Copy)]
+pub(super) struct Occasion {
+    /// The other player involved: who dealt or who took the damage
+    pub other: Option<Seat>,
+    pub card: Option<CardId>,
+    pub amount: u32,
+}
+
+/// The hooks the engine asks before applying a rule, so the skills of the characters can
+/// change the outcome.
+impl Engine {
+    /// Number of cards the player draws in the draw phase.
+    pub(super) fn draw_phase_cards(&self, seat: Seat) -> usize {
+        if self.state.has_skill(&self.database, seat, "draw_extra") {
+            DRAW_PHASE_CARDS + 1
+        } else {
+            DRAW_PHASE_CARDS
+        }
+    }
+
+    /// Number of cards the player may keep at the end of the turn.
+    pub(super) fn hand_limit(&self, seat: Seat) -> usize {
+        let health = self.state.players[seat].health.max(0) as usize;
+        if self
+            .state
+            .has_skill(&self.database, seat, "hand_limit_plus_two")
+        {
+            health + 2
+        } else {
+            health
+        }
+    }
+
+    /// The damage a player deals with a card, after the skills of the player.
+    pub(super) fn damage_amount(
+        &self,
+        source: Option<Seat>,
+        card: Option<CardId>,
+        amount: u32,
+    ) -> u32 {
+        let attack = match card.and_then(|id| self.database.card(id)) {
+            Some(card) => self.is_attack(&card),
+            None => false,
+        };
+        match source {
+            Some(seat) if attack && self.state.has_skill(&self.database, seat, "heavy_attack") => {
+                amount + 1
+            }
+            _ => amount,
+        }
+    }
+
+    /// Whether the player can answer with the card where a card with the `answer` effect is
+    /// asked for.
+    pub(super) fn answers(&self, seat: Seat, card: &Card, answer: &str) -> bool {
+        if self.has_effect(card, answer) {
+            return true;
+        }
+        let swapped = match answer {
+            "cancel_attack" => self.is_attack(card),
+            "damage" => self.has_effect(card, "cancel_attack"),
+            _ => false,
+        };
+        swapped
+            && self
+                .state
+                .has_skill(&self.database, seat, "attack_as_dodge")
+    }
+
+    /// Whether the player may play more than one Attack in a turn.
+    pub(super) fn unlimited_attacks(&self, seat: Seat) -> bool {
+        let weapon = match &self.state.players[seat].equipment.weapon {
+            Some(weapon) => self.has_effect(weapon, "unlimited_attacks"),
+            None => false,
+        };
+        weapon
+            || self
+                .state
+                .has_skill(&self.database, seat, "unlimited_attacks")
+    }
+
+    pub(super) fn is_attack(&self, card: &Card) -> bool {
+        card.category == Category::Basic && self.has_effect(card, "damage")
+    }
+
+    /// Use the triggered skills of the player that react to what happened.
+    pub(super) fn trigger(&mut self, seat: Seat, trigger: Trigger, occasion: Occasion) {
+        let skills: Vec<(String, String)> = self
+            .state
+            .skills(&self.database, seat)
+            .filter(|skill| skill.kind == SkillKind::Triggered(trigger))
+            .map(|skill| (skill.name.clone(), skill.effect.clone()))
+            .collect();
+        for (name, effect) in skills {
+            if !self.state.players[seat].alive {
+                return;
+            }
+            match effect.as_str() {
+                "draw_on_damage" => {
+                    self.announce_skill(seat, name);
+                    self.draw(seat, occasion.amount as usize);
+                }
+                "freeze" => {
+                    let attack = match occasion.card.and_then(|id| self.database.card(id)) {
+                        Some(card) => self.is_attack(&card),
+                        None => false,
+                    };
+                    let target = match occasion.other {
+                        Some(target) if attack => target,
+                        _ => continue,
+                    };
+                    let count = self.state.players[target].hand.len();
+                    if count == 0 {
+                        continue;
+                    }
+                    let index = self.state.deck.pick(count);
+                    let card = self.state.players[target].hand.cards()[index].id;
+                    self.announce_skill(seat, name);
+                    self.emit(GameEvent::CardsDiscarded {
+                        player: self.state.players[target].name.clone(),
+                        cards: vec![card],
+                    });
+                }
+                other => log::debug!("The skill effect {} is not resolved yet", other),
+            }
+        }
+    }
+
+    /// Let the skills of the judged player change the judgment before the outcome is
+    /// decided.
+    pub(super) fn modify_judgment(&mut self, judgment: &mut Judgment) {
+        let seat = judgment.seat;
+        let skills: Vec<(String, String)> = self
+            .state
+            .skills(&self.database, seat)
+            .filter(|skill| skill.kind == SkillKind::Triggered(Trigger::Judgment))
+            .map(|skill| (skill.name.clone(), skill.effect.clone()))
+            .collect();
+        for (name, effect) in skills {
+            match effect.as_str() {
+                // Flip once more if the first card would hit, the second one counts
+                "rejudge" if judgment.hits() => {
+                    self.announce_skill(seat, name);
+                    if let Some(card) = self.flip(seat, &judgment.trick) {
+                        judgment.flipped = card;
+                    }
+                }
+                "rejudge" => {}
+                other => log::debug!("The skill effect {} is not resolved yet", other),
+            }
+        }
+    }
+
+    fn announce_skill(&mut self, seat: Seat, skill: String) {
+        self.emit(GameEvent::SkillUsed {
+            player: self.state.players[seat].name.clone(),
+            skill,
+        });
+    }
+
+    /// Use an active skill of the player, with the chosen cards from hand and targets.
+    pub(super) fn use_skill(
+        &mut self,
+        seat: Seat,
+        skill: String,
+        cards: Vec<CardId>,
+        targets: Vec<String>,
+    ) -> Result<(), ActionError> {
+        let effect = self
+            .state
+            .skills(&self.database, seat)
+            .find(|s| s.name == skill && s.kind == SkillKind::Active)
+            .map(|s| s.effect.clone())
+            .ok_or_else(|| ActionError::UnknownSkill(skill.clone()))?;
+        if self.skills_used.contains(&skill) {
+            return Err(ActionError::SkillUsed(skill));
+        }
+        if let Some(missing) = cards
+            .iter()
+            .find(|c| !self.state.players[seat].hand.contains(**c))
+        {
+            return Err(ActionError::NotInHand(*missing));
+        }
+        let mut seats = Vec::with_capacity(targets.len());
+        for name in &targets {
+            match self.state.seat_of(name) {
+                Some(target) if self.state.players[target].alive => seats.push(target),
+                _ => return Err(ActionError::UnknownTarget(name.clone())),
+            }
+        }
+
+        match (effect.as_str(), cards.as_slice(), seats.as_slice()) {
+            ("give_card_draw", [card], [target]) if *target != seat => {
+                self.skills_used.push(skill.clone());
+                self.announce_skill(seat, skill);
+                self.emit(GameEvent::CardGiven {
+                    player: self.state.players[seat].name.clone(),
+                    to: targets[0].clone(),
+                    card: Some(*card),
+                });
+                self.draw(seat, 1);
+                Ok(())
+            }
+            ("give_card_draw", _, _) => Err(ActionError::InvalidTargets),
+            (other, _, _) => {
+                log::debug!("The skill effect {} is not resolved yet", other);
+                Err(ActionError::UnknownSkill(skill))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{
+        tests::{card, game, hand, lay, pass_all, play, respond, setup},
+        ActionError, OPENING_HAND,
+    };
+    use super::*;
+    use crate::msg::GameAction;
+
+    fn skill(name: &str, cards: Vec<CardId>, targets: &[&str]) -> GameAction {
+        GameAction::UseSkill {
+            skill: name.to_string(),
+            cards,
+            targets: targets.iter().map(|t| t.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn skills_change_the_draw_and_the_hand_limit() {
+        let engine = game(&[Some("Kanako"), None], &[]);
+        let drawn = OPENING_HAND + DRAW_PHASE_CARDS + 1;
+        assert_eq!(hand(&engine, 0).len(), drawn);
+
+        // Three health and two more cards to keep
+        let mut engine = game(&[Some("Flandre"), None], &[]);
+        let events = engine.act("alice", GameAction::EndPhase).unwrap();
+        let requested = GameEvent::DiscardRequested {
+            player: "alice".to_string(),
+            count: 1,
+        };
+        assert_eq!(events.last(), Some(&requested));
+    }
+
+    #[test]
+    fn heavy_attacks_deal_more_damage() {
+        let mut engine = game(&[Some("Flandre"), None], &["Attack"]);
+        let attack = card(&engine, 0, "Attack");
+        engine.act("alice", play(attack, &["bob"])).unwrap();
+        pass_all(&mut engine);
+        assert_eq!(engine.state().players[1].health, 2);
+    }
+
+    #[test]
+    fn masks_dodge_with_attacks_and_draw_on_damage() {
+        let top = [
+            "Attack", "Attack", "Crossbow", "Peach", "Attack", "Peach", "Peach", "Peach",
+        ];
+        let mut engine = game(&[None, Some("Kokoro")], &top);
+        let crossbow = card(&engine, 0, "Crossbow");
+        engine.act("alice", play(crossbow, &[])).unwrap();
+        let attack = card(&engine, 0, "Attack");
+        engine.act("alice", play(attack, &["bob"])).unwrap();
+        let dodge = card(&engine, 1, "Attack");
+        respond(&mut engine, Some(dodge));
+        assert_eq!(engine.state().players[1].health, 3);
+
+        let attack = card(&engine, 0, "Attack");
+        engine.act("alice", play(attack, &["bob"])).unwrap();
+        let events = respond(&mut engine, None);
+        let used = GameEvent::SkillUsed {
+            player: "bob".to_string(),
+            skill: "Mask of Anger".to_string(),
+        };
+        assert!(events.contains(&used));
+        assert_eq!(hand(&engine, 1).len(), OPENING_HAND);
+    }
+
+    #[test]
+    fn a_freezing_attack_throws_away_a_card() {
+        let mut engine = game(&[Some("Cirno"), None], &["Attack"]);
+        let attack = card(&engine, 0, "Attack");
+        engine.act("alice", play(attack, &["bob"])).unwrap();
+        let events = pass_all(&mut engine);
+        let used = GameEvent::SkillUsed {
+            player: "alice".to_string(),
+            skill: "Perfect Freeze".to_string(),
+        };
+        assert!(events.contains(&used));
+        assert!(matches!(
+            events.last(),
+            Some(GameEvent::CardsDiscarded { player, cards }) if player == "bob" && cards.len() == 1
+        ));
+        assert_eq!(hand(&engine, 1).len(), OPENING_HAND - 1);
+    }
+
+    #[test]
+    fn puppeteer_gives_a_card_once_per_turn() {
+        let mut engine = game(&[Some("Alice"), None], &["Peach", "Peach"]);
+        let peaches = hand(&engine, 0)[..2].to_vec();
+        assert_eq!(
+            engine.act("alice", skill("Doll Army", vec![peaches[0]], &["bob"])),
+            Err(ActionError::UnknownSkill("Doll Army".to_string()))
+        );
+        assert_eq!(
+            engine.act("alice", skill("Puppeteer", vec![peaches[0]], &["alice"])),
+            Err(ActionError::InvalidTargets)
+        );
+
+        let events = engine
+            .act("alice", skill("Puppeteer", vec![peaches[0]], &["bob"]))
+            .unwrap();
+        let given = GameEvent::CardGiven {
+            player: "alice".to_string(),
+            to: "bob".to_string(),
+            card: Some(peaches[0]),
+        };
+        assert!(events.contains(&given));
+        assert!(hand(&engine, 1).contains(&peaches[0]));
+        assert_eq!(hand(&engine, 0).len(), OPENING_HAND + DRAW_PHASE_CARDS);
+        assert_eq!(
+            engine.act("alice", skill("Puppeteer", vec![peaches[1]], &["bob"])),
+            Err(ActionError::SkillUsed("Puppeteer".to_string()))
+        );
+    }
+
+    #[test]
+    fn a_second_judgment_turns_the_lightning_away() {
+        // The first card flipped would strike, the second one does not
+        let top = [
+            "Dodge", "Dodge", "Dodge", "Dodge", "Dodge", "Dodge", "Peach", "Peach", "Attack",
+            "Peach",
+        ];
+        let mut engine = setup(&[Some("Kanako"), None], &top);
+        lay(&mut engine, 0, "Lightning");
+        engine.start();
+        // Nobody nullifies the lightning
+        respond(&mut engine, None);
+        let events = respond(&mut engine, None);
+        let used = GameEvent::SkillUsed {
+            player: "alice".to_string(),
+            skill: "Divine Oracle".to_string(),
+        };
+        assert!(events.contains(&used));
+        let (flipped, hit) = events
+            .iter()
+            .find_map(|event| match event {
+                GameEvent::Judged { card, hit, .. } => Some((*card, *hit)),
+                _ => None,
+            })
+            .expect("Nothing was judged");
+        let flipped = engine.database().card(flipped).unwrap();
+        assert_eq!((flipped.name.as_str(), hit), ("Peach", false));
+        assert_eq!(engine.state().players[0].health, 4);
+        assert_eq!(engine.state().players[1].judgment.len(), 1);
+    }
+}
@@ -0,0 +1,422 @@
+use std::collections::VecDeque;
+
+use super::{ActionError, Engine};
+use crate::{
+    msg::Prompt,
+    rules::{
+        card::{Card, CardId, Category},
+        event::GameEvent,
+        player::Seat,
+    },
+};
+
+/// One effect of a played card on one of its targets, waiting to be carried out.
+#[derive(Debug, Clone)]
+pub(super) struct Effect {
+    pub source: Seat,
+    pub card: Card,
+    pub target: Seat,
+    pub effect: String,
+    /// Everybody may still cancel the trick
+    pub negatable: bool,
+    /// The target may still avoid the effect with a card that has this effect
+    pub answer: Option<&'static str>,
+}
+
+impl Effect {
+    pub fn new(source: Seat, card: &Card, target: Seat, effect: &str) -> Self {
+        let answer = match effect {
+            "damage" | "arrows_all" => Some("cancel_attack"),
+            // Answered with an attack
+            "attack_all" => Some("damage"),
+            _ => None,
+        };
+        Effect {
+            source,
+            card: card.clone(),
+            target,
+            effect: effect.to_string(),
+            negatable: matches!(card.category, Category::Trick | Category::DelayedTrick),
+            answer,
+        }
+    }
+
+    /// The other one of the two players of the effect.
+    pub fn opponent(&self, seat: Seat) -> Seat {
+        if seat == self.target {
+            self.source
+        } else {
+            self.target
+        }
+    }
+}
+
+/// Why the players are asked.
+#[derive(Debug, Clone)]
+pub(super) enum Reason {
+    /// The effect does not happen if somebody answers with a card that has the `answer`
+    /// effect. In a chain the answer can be answered again the same way, and every answer
+    /// turns the outcome around.
+    Cancel {
+        effect: Effect,
+        answer: &'static str,
+        chain: bool,
+        cancelled: bool,
+    },
+    /// The dying player is saved with a healing card.
+    Rescue { dying: Seat, source: Option<Seat> },
+    /// The two players of a duel answer with an Attack in turn, the target first. The
+    /// `asked` one takes the damage if the player does not.
+    Duel { effect: Effect, asked: Seat },
+}
+
+/// A pause of the turn in which players are asked one after the other, in seat order, to
+/// answer with a card or to pass.
+#[derive(Debug, Clone)]
+pub(super) struct Window {
+    reason: Reason,
+    /// The first one is asked right now
+    asking: VecDeque<Seat>,
+}
+
+impl Engine {
+    /// Ask the given players, in this order, until somebody answers.
+    pub(super) fn open_window(&mut self, reason: Reason, asking: Vec<Seat>) {
+        let window = Window {
+            reason,
+            asking: asking.into_iter().collect(),
+        };
+        if window.asking.is_empty() {
+            self.close_window(window);
+        } else {
+            self.window = Some(window);
+            self.ask();
+        }
+    }
+
+    fn ask(&mut self) {
+        if let Some(seat) = self.asked() {
+            self.emit(GameEvent::ResponseRequested {
+                player: self.state.players[seat].name.clone(),
+            });
+        }
+    }
+
+    /// The player who has to answer before the game goes on, if any.
+    pub(super) fn asked(&self) -> Option<Seat> {
+        self.window.as_ref().and_then(|w| w.asking.front().copied())
+    }
+
+    /// What the asked player has to answer, or the discard the current player has to
+    /// make.
+    pub fn prompt(&self) -> Option<(&str, Prompt)> {
+        let window = match self.window.as_ref() {
+            Some(window) => window,
+            None => return self.discard_prompt(),
+        };
+        let seat = *window.asking.front()?;
+        let name = |seat: Seat| self.state.players[seat].name.clone();
+        let prompt = match &window.reason {
+            Reason::Cancel { effect, answer, .. } => Prompt::Cancel {
+                card: effect.card.id,
+                source: name(effect.source),
+                target: name(effect.target),
+                answer: answer.to_string(),
+            },
+            Reason::Rescue { dying, .. } => Prompt::Rescue {
+                dying: name(*dying),
+            },
+            Reason::Duel { effect, asked } => Prompt::Cancel {
+                card: effect.card.id,
+                source: name(effect.opponent(*asked)),
+                target: name(*asked),
+                answer: "damage".to_string(),
+            },
+        };
+        Some((&self.state.players[seat].name, prompt))
+    }
+
+    /// The asked player answers with a card, or passes with `None`.
+    pub(super) fn respond(&mut self, seat: Seat, card: Option<CardId>) -> Result<(), ActionError> {
+        let answer = match self.window.as_ref().map(|w| &w.reason) {
+            Some(Reason::Cancel { answer, .. }) => *answer,
+            Some(Reason::Rescue { .. }) => "heal",
+            Some(Reason::Duel { .. }) => "damage",
+            None => return Err(ActionError::Unexpected),
+        };
+        if let Some(card) = card {
+            let fits = match self.state.players[seat]
+                .hand
+                .cards()
+                .iter()
+                .find(|c| c.id == card)
+            {
+                Some(c) => self.answers(seat, c, answer),
+                None => return Err(ActionError::NotInHand(card)),
+            };
+            if !fits {
+                return Err(ActionError::NotPlayable(card));
+            }
+        }
+        let mut window = self.window.take().unwrap();
+        self.emit(GameEvent::Responded {
+            player: self.state.players[seat].name.clone(),
+            card,
+        });
+        if card.is_none() {
+            window.asking.pop_front();
+            if window.asking.is_empty() {
+                self.close_window(window);
+            } else {
+                self.window = Some(window);
+                self.ask();
+            }
+            return Ok(());
+        }
+
+        match window.reason {
+            Reason::Cancel {
+                chain: true,
+                cancelled,
+                effect,
+                answer,
+            } => {
+                // Everybody else may answer the answer
+                let current = self.state.turn.current;
+                let asking = self.seat_order(current).filter(|s| *s != seat).collect();
+                let reason = Reason::Cancel {
+                    chain: true,
+                    cancelled: !cancelled,
+                    effect,
+                    answer,
+                };
+                self.open_window(reason, asking);
+            }
+            Reason::Cancel { effect, answer, .. } => {
+                let reason = Reason::Cancel {
+                    chain: false,
+                    cancelled: true,
+                    effect,
+                    answer,
+                };
+                window.reason = reason;
+                self.close_window(window);
+            }
+            Reason::Rescue { dying, .. } => {
+                self.heal(dying, Some(seat), 1);
+                if self.state.players[dying].is_dying() {
+                    // The same player may give more
+                    self.window = Some(window);
+                    self.ask();
+                } else {
+                    self.emit(GameEvent::Rescued {
+                        player: self.state.players[dying].name.clone(),
+                    });
+                }
+            }
+            Reason::Duel { effect, asked } => {
+                let asked = effect.opponent(asked);
+                self.open_window(Reason::Duel { effect, asked }, vec![asked]);
+            }
+        }
+        Ok(())
+    }
+
+    /// Nobody answers anymore: the effect goes on or is cancelled, the dying player dies.
+    fn close_window(&mut self, window: Window) {
+        match window.reason {
+            Reason::Cancel {
+                effect,
+                cancelled: true,
+                ..
+            } => {
+                self.emit(GameEvent::Cancelled {
+                    card: effect.card.id,
+                    target: self.state.players[effect.target].name.clone(),
+                });
+                if self.has_effect(&effect.card, "lightning") {
+                    self.pass_trick(effect.target, &effect.card);
+                }
+            }
+            Reason::Cancel { effect, .. } => self.effects.push_front(effect),
+            Reason::Rescue { dying, source } => self.die(dying, source),
+            Reason::Duel { effect, asked } => {
+                let winner = effect.opponent(asked);
+                self.damage(asked, Some(winner), Some(effect.card.id), 1);
+            }
+        }
+    }
+
+    /// Carry out an effect, after giving the players the chance to cancel it.
+    pub(super) fn start_effect(&mut self, mut effect: Effect) {
+        if !self.state.players[effect.target].alive {
+            return;
+        }
+        if effect.negatable {
+            effect.negatable = false;
+            let current = self.state.turn.current;
+            let asking = self.seat_order(current).collect();
+            let reason = Reason::Cancel {
+                effect,
+                answer: "cancel_trick",
+                chain: true,
+                cancelled: false,
+            };
+            self.open_window(reason, asking);
+        } else if let Some(answer) = effect.answer.take() {
+            let asking = vec![effect.target];
+            let reason = Reason::Cancel {
+                effect,
+                answer,
+                chain: false,
+                cancelled: false,
+            };
+            self.open_window(reason, asking);
+        } else {
+            self.apply(effect);
+        }
+    }
+
+    fn apply(&mut self, effect: Effect) {
+        if effect.card.category == Category::DelayedTrick {
+            self.judge(effect);
+            return;
+        }
+        if effect.effect == "duel" {
+            let asked = effect.target;
+            self.open_window(Reason::Duel { effect, asked }, vec![asked]);
+            return;
+        }
+        let Effect {
+            source,
+            card,
+            target,
+            ..
+        } = effect;
+        match effect.effect.as_str() {
+            "damage" | "arrows_all" | "attack_all" => {
+                self.damage(target, Some(source), Some(card.id), 1)
+            }
+            "heal" | "heal_all" => self.heal(target, Some(source), 1),
+            "draw_two" => self.draw(source, 2),
+            "discard_one" => self.dismantle(target),
+            "steal_one" => self.snatch(source, target),
+            other => log::debug!("The effect {} of {} is not resolved yet", other, card),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{
+        tests::{card, game, hand, play, respond},
+        ActionError,
+    };
+    use crate::{
+        msg::{GameAction, Prompt},
+        rules::event::GameEvent,
+    };
+
+    #[test]
+    fn a_dodge_cancels_the_attack() {
+        let top = [
+            "Attack", "Attack", "Attack", "Attack", "Dodge", "Peach", "Peach", "Peach",
+        ];
+        let mut engine = game(&[None, None], &top);
+        let attack = card(&engine, 0, "Attack");
+        engine.act("alice", play(attack, &["bob"])).unwrap();
+        let prompt = Prompt::Cancel {
+            card: attack,
+            source: "alice".to_string(),
+            target: "bob".to_string(),
+            answer: "cancel_attack".to_string(),
+        };
+        assert_eq!(engine.prompt(), Some(("bob", prompt)));
+
+        let peach = card(&engine, 1, "Peach");
+        let respond_with = |card| GameAction::Respond { card: Some(card) };
+        assert_eq!(
+            engine.act("bob", respond_with(peach)),
+            Err(ActionError::NotPlayable(peach))
+        );
+        assert_eq!(
+            engine.act("bob", respond_with(attack)),
+            Err(ActionError::NotInHand(attack))
+        );
+        let dodge = card(&engine, 1, "Dodge");
+        let events = engine.act("bob", respond_with(dodge)).unwrap();
+        let cancelled = GameEvent::Cancelled {
+            card: attack,
+            target: "bob".to_string(),
+        };
+        assert!(events.contains(&cancelled));
+        assert_eq!(engine.asked(), None);
+        assert_eq!(engine.state().players[1].health, 4);
+    }
+
+    #[test]
+    fn everybody_waits_for_the_asked_player() {
+        let mut engine = game(&[None, None, None], &["Attack"]);
+        let attack = card(&engine, 0, "Attack");
+        engine.act("alice", play(attack, &["bob"])).unwrap();
+        let waiting = Err(ActionError::WaitingFor("bob".to_string()));
+        assert_eq!(engine.act("alice", GameAction::EndPhase), waiting);
+        let pass = GameAction::Respond { card: None };
+        assert_eq!(engine.act("carol", pass), waiting);
+
+        // Taking too long is passing
+        let events = engine.time_out();
+        let passed = GameEvent::Responded {
+            player: "bob".to_string(),
+            card: None,
+        };
+        assert_eq!(events.first(), Some(&passed));
+        assert_eq!(engine.state().players[1].health, 3);
+    }
+
+    #[test]
+    fn every_nullify_turns_the_outcome_around() {
+        let top = [
+            "Dismantle",
+            "Dismantle",
+            "Nullify",
+            "Peach",
+            "Nullify",
+            "Nullify",
+            "Nullify",
+            "Peach",
+        ];
+        let mut engine = game(&[None, None], &top);
+
+        // Nullified, and the nullify nullified: the dismantle goes on
+        let dismantle = card(&engine, 0, "Dismantle");
+        engine.act("alice", play(dismantle, &["bob"])).unwrap();
+        respond(&mut engine, None);
+        let nullify = card(&engine, 1, "Nullify");
+        respond(&mut engine, Some(nullify));
+        assert_eq!(engine.asked(), Some(0));
+        let nullify = card(&engine, 0, "Nullify");
+        respond(&mut engine, Some(nullify));
+        assert_eq!(engine.asked(), Some(1));
+        let events = respond(&mut engine, None);
+        assert!(matches!(
+            events.last(),
+            Some(GameEvent::CardsDiscarded { player, .. }) if player == "bob"
+        ));
+        let left = hand(&engine, 1).len();
+
+        // Nullified once: nothing happens
+        let dismantle = card(&engine, 0, "Dismantle");
+        engine.act("alice", play(dismantle, &["bob"])).unwrap();
+        respond(&mut engine, None);
+        let nullify = card(&engine, 1, "Nullify");
+        respond(&mut engine, Some(nullify));
+        let events = respond(&mut engine, None);
+        let cancelled = GameEvent::Cancelled {
+            card: dismantle,
+            target: "bob".to_string(),
+        };
+        assert_eq!(events.last(), Some(&cancelled));
+        assert_eq!(hand(&engine, 1).len(), left - 1);
+    }
+}
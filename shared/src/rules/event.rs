@@ -0,0 +1,557 @@
+use serde::{Deserialize, Serialize};
+
+use super::{
+    card::CardId,
+    database::CardDatabase,
+    game::GameState,
+    role::Role,
+    turn::{Phase, TurnState},
+    zone::Zone,
+};
+
+/// One change of the game. Everything that happens at the table is an event, and the state
+/// of a game is what applying its events in order gives, see `GameState::apply`. The
+/// engine applies each event to its own state as it happens, the server sends the events to
+/// the clients, which apply them to their view of the game.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub enum GameEvent {
+    /// A game started with the given players, in turn order, their characters and the
+    /// health they start with.
+    GameStarted {
+        players: Vec<String>,
+        characters: Vec<Option<String>>,
+        health: Vec<i32>,
+    },
+    /// The role of the player. Only the leader is told to everybody, the others only to
+    /// the player.
+    RoleAssigned {
+        player: String,
+        role: Role,
+    },
+    TurnStarted {
+        player: String,
+        round: u32,
+    },
+    PhaseChanged {
+        player: String,
+        phase: Phase,
+    },
+    /// The player took `count` cards from the top of the draw pile. Only the player is told
+    /// which `cards`.
+    CardsDrawn {
+        player: String,
+        count: u32,
+        cards: Vec<CardId>,
+    },
+    CardPlayed {
+        player: String,
+        card: CardId,
+        targets: Vec<String>,
+    },
+    /// The player put an equipment card into its slot, replacing the card that was there.
+    Equipped {
+        player: String,
+        card: CardId,
+        replaced: Option<CardId>,
+    },
+    /// An equipment card was taken out of its slot and thrown away, such as by Dismantle.
+    Unequipped {
+        player: String,
+        card: CardId,
+    },
+    /// The player put a delayed trick in front of the target.
+    TrickPlaced {
+        player: String,
+        card: CardId,
+        target: String,
+    },
+    /// The top card of the draw pile was flipped to judge the delayed trick of the player.
+    JudgmentFlipped {
+        player: String,
+        trick: CardId,
+        card: CardId,
+    },
+    /// The judgment is final: `card` decided whether the trick takes effect.
+    Judged {
+        player: String,
+        trick: CardId,
+        card: CardId,
+        hit: bool,
+    },
+    /// A delayed trick moved on to the judgment zone of the next player.
+    TrickPassed {
+        card: CardId,
+        from: String,
+        to: String,
+    },
+    /// The discard pile was shuffled into the empty draw pile, which is now `order` from
+    /// the bottom to the top. The order is kept from the clients.
+    DeckReshuffled {
+        cards: u32,
+        order: Vec<CardId>,
+    },
+    CardsDiscarded {
+        player: String,
+        cards: Vec<CardId>,
+    },
+    /// The player handed a card from hand to another player. The `card` is only shown to the
+    /// two of them.
+    CardGiven {
+        player: String,
+        to: String,
+        card: Option<CardId>,
+    },
+    /// The player took the card from the equipment or the judgment zone of `from` into hand.
+    CardTaken {
+        player: String,
+        from: String,
+        card: CardId,
+    },
+    /// A skill of the character of the player came into play.
+    SkillUsed {
+        player: String,
+        skill: String,
+    },
+    /// The player lost health, `source` is the player who caused it.
+    Damaged {
+        player: String,
+        source: Option<String>,
+        card: Option<CardId>,
+        amount: u32,
+        health: i32,
+    },
+    Healed {
+        player: String,
+        source: Option<String>,
+        amount: u32,
+        health: i32,
+    },
+    /// The player ran out of health and dies unless somebody helps.
+    Dying {
+        player: String,
+        health: i32,
+    },
+    /// The game waits for `player` to respond, the player is sent a `Prompt`.
+    ResponseRequested {
+        player: String,
+    },
+    /// The player has more cards than the hand limit and has to discard `count` of them,
+    /// the player is sent a `Prompt`.
+    DiscardRequested {
+        player: String,
+        count: u32,
+    },
+    /// The asked player answered with a card, or passed.
+    Responded {
+        player: String,
+        card: Option<CardId>,
+    },
+    /// Somebody responded in time, `card` has no effect on `target`. A delayed trick is also
+    /// cancelled when it is thrown away before its judgment.
+    Cancelled {
+        card: CardId,
+        target: String,
+    },
+    /// The dying player got back above zero health.
+    Rescued {
+        player: String,
+    },
+    /// The player is out of the game. The role and all cards the player had are shown, the
+    /// cards are discarded.
+    PlayerDied {
+        player: String,
+        killer: Option<String>,
+        role: Option<Role>,
+        revealed: Vec<CardId>,
+    },
+    /// The game is decided. Everybody's role is shown.
+    GameOver {
+        winners: Vec<String>,
+        roles: Vec<(String, Option<Role>)>,
+    },
+}
+
+impl GameEvent {
+    /// What the named player may know of the event, `None` if nothing. Without a name the
+    /// event is viewed by a spectator, see `spectator_view`.
+    pub fn view(&self, viewer: Option<&str>) -> Option<GameEvent> {
+        match viewer {
+            Some(viewer) => self.player_view(viewer),
+            None => self.spectator_view(),
+        }
+    }
+
+    /// What a player at the table may know of the event. The others only learn how many
+    /// cards a player draws or is given, roles are kept until they are shown and the order
+    /// of the draw pile is kept from everybody.
+    fn player_view(&self, viewer: &str) -> Option<GameEvent> {
+        let event = match self {
+            GameEvent::RoleAssigned { player, role } if !role.is_revealed() && player != viewer => {
+                return None
+            }
+            GameEvent::CardsDrawn { player, count, .. } if player != viewer => {
+                GameEvent::CardsDrawn {
+                    player: player.clone(),
+                    count: *count,
+                    cards: vec![],
+                }
+            }
+            GameEvent::DeckReshuffled { cards, .. } => GameEvent::DeckReshuffled {
+                cards: *cards,
+                order: vec![],
+            },
+            GameEvent::CardGiven { player, to, .. } if player != viewer && to != viewer => {
+                GameEvent::CardGiven {
+                    player: player.clone(),
+                    to: to.clone(),
+                    card: None,
+                }
+            }
+            event => event.clone(),
+        };
+        Some(event)
+    }
+
+    /// What somebody watching without a seat may know of the event, which is less than the
+    /// players learn. Spectators do not follow the hands at all, not even how many cards
+    /// there are, and learn no hidden role before the game is over: the role and the cards
+    /// a dead player reveals are only shown at the table.
+    fn spectator_view(&self) -> Option<GameEvent> {
+        let event = match self {
+            GameEvent::RoleAssigned { role, .. } if !role.is_revealed() => return None,
+            GameEvent::CardsDrawn { .. } | GameEvent::CardGiven { .. } => return None,
+            GameEvent::DeckReshuffled { cards, .. } => GameEvent::DeckReshuffled {
+                cards: *cards,
+                order: vec![],
+            },
+            GameEvent::PlayerDied { player, killer, .. } => GameEvent::PlayerDied {
+                player: player.clone(),
+                killer: killer.clone(),
+                role: None,
+                revealed: vec![],
+            },
+            event => event.clone(),
+        };
+        Some(event)
+    }
+}
+
+impl GameState {
+    /// Carry out the event. A `GameStarted` event seats new players, unless they are seated
+    /// already. A view of the game that does not know where a card was, such as the draw
+    /// pile of a client, looks the card up in the database instead.
+    pub fn apply(&mut self, event: &GameEvent, database: &CardDatabase) {
+        match event {
+            GameEvent::GameStarted {
+                players,
+                characters,
+                health,
+            } => {
+                if !self.players.iter().map(|p| &p.name).eq(players.iter()) {
+                    *self = GameState::new(players.clone(), vec![], 0);
+                }
+                for (seat, character) in characters.iter().enumerate() {
+                    if let Some(character) = character {
+                        self.assign_character(database, seat, character);
+                    }
+                }
+                for (player, health) in self.players.iter_mut().zip(health) {
+                    player.max_health = *health;
+                    player.health = *health;
+                }
+            }
+            GameEvent::RoleAssigned { player, role } => {
+                if let Some(seat) = self.seat_of(player) {
+                    self.players[seat].role = Some(*role);
+                }
+            }
+            GameEvent::TurnStarted { player, round } => {
+                if let Some(seat) = self.seat_of(player) {
+                    self.turn = TurnState {
+                        round: *round,
+                        current: seat,
+                        phase: Phase::Start,
+                    };
+                }
+            }
+            GameEvent::PhaseChanged { phase, .. } => self.turn.phase = *phase,
+            GameEvent::CardsDrawn {
+                player,
+                count,
+                cards,
+            } => {
+                if let Some(seat) = self.seat_of(player) {
+                    for card in cards {
+                        self.relocate(database, *card, Zone::DrawPile, Zone::Hand(seat));
+                    }
+                    let unknown = (*count as usize).saturating_sub(cards.len());
+                    self.players[seat].hidden_cards += unknown as u32;
+                }
+            }
+            GameEvent::CardPlayed { player, card, .. } => {
+                if let Some(seat) = self.seat_of(player) {
+                    self.relocate(database, *card, Zone::Hand(seat), Zone::DiscardPile);
+                }
+            }
+            GameEvent::Equipped { player, card, .. } => {
+                if let Some(seat) = self.seat_of(player) {
+                    self.relocate(database, *card, Zone::Hand(seat), Zone::Equipment(seat));
+                }
+            }
+            GameEvent::Unequipped { player, card } => {
+                if let Some(seat) = self.seat_of(player) {
+                    self.relocate(database, *card, Zone::Equipment(seat), Zone::DiscardPile);
+                }
+            }
+            GameEvent::TrickPlaced {
+                player,
+                card,
+                target,
+            } => {
+                if let (Some(seat), Some(target)) = (self.seat_of(player), self.seat_of(target)) {
+                    self.relocate(database, *card, Zone::Hand(seat), Zone::Judgment(target));
+                }
+            }
+            GameEvent::JudgmentFlipped { card, .. } => {
+                self.relocate(database, *card, Zone::DrawPile, Zone::DiscardPile)
+            }
+            GameEvent::Judged { player, trick, .. } => {
+                if let Some(seat) = self.seat_of(player) {
+                    self.relocate(database, *trick, Zone::Judgment(seat), Zone::DiscardPile);
+                }
+            }
+            GameEvent::TrickPassed { card, from, to } => {
+                if let (Some(from), Some(to)) = (self.seat_of(from), self.seat_of(to)) {
+                    // A judged or cancelled trick is on the discard pile already
+                    let from = if self.players[from].judgment.contains(*card) {
+                        Zone::Judgment(from)
+                    } else {
+                        Zone::DiscardPile
+                    };
+                    self.relocate(database, *card, from, Zone::Judgment(to));
+                }
+            }
+            GameEvent::DeckReshuffled { order, .. } => {
+                let mut cards = self.deck.discard_pile.take_all();
+                for id in order {
+                    if let Some(index) = cards.iter().position(|c| c.id == *id) {
+                        self.deck.draw_pile.push(cards.remove(index));
+                    }
+                }
+                // Without the order the draw pile is not known anyway
+                for card in cards {
+                    self.deck.draw_pile.push(card);
+                }
+            }
+            GameEvent::CardsDiscarded { player, cards } => {
+                if let Some(seat) = self.seat_of(player) {
+                    for card in cards {
+                        self.relocate(database, *card, Zone::Hand(seat), Zone::DiscardPile);
+                    }
+                }
+            }
+            GameEvent::CardGiven { player, to, card } => {
+                if let (Some(seat), Some(to)) = (self.seat_of(player), self.seat_of(to)) {
+                    match card {
+                        Some(card) => {
+                            self.relocate(database, *card, Zone::Hand(seat), Zone::Hand(to))
+                        }
+                        None => {
+                            let from = &mut self.players[seat].hidden_cards;
+                            *from = from.saturating_sub(1);
+                            self.players[to].hidden_cards += 1;
+                        }
+                    }
+                }
+            }
+            GameEvent::CardTaken { player, from, card } => {
+                if let (Some(seat), Some(from)) = (self.seat_of(player), self.seat_of(from)) {
+                    let zone = if self.players[from].judgment.contains(*card) {
+                        Zone::Judgment(from)
+                    } else {
+                        Zone::Equipment(from)
+                    };
+                    self.relocate(database, *card, zone, Zone::Hand(seat));
+                }
+            }
+            GameEvent::Damaged { player, health, .. }
+            | GameEvent::Healed { player, health, .. } => {
+                if let Some(seat) = self.seat_of(player) {
+                    self.players[seat].health = *health;
+                }
+            }
+            GameEvent::Responded {
+                player,
+                card: Some(card),
+            } => {
+                if let Some(seat) = self.seat_of(player) {
+                    self.relocate(database, *card, Zone::Hand(seat), Zone::DiscardPile);
+                }
+            }
+            GameEvent::Cancelled { card, target } => {
+                // A cancelled delayed trick leaves the judgment zone
+                if let Some(seat) = self.seat_of(target) {
+                    if self.players[seat].judgment.contains(*card) {
+                        self.relocate(database, *card, Zone::Judgment(seat), Zone::DiscardPile);
+                    }
+                }
+            }
+            GameEvent::PlayerDied {
+                player,
+                role,
+                revealed,
+                ..
+            } => {
+                if let Some(seat) = self.seat_of(player) {
+                    let player = &mut self.players[seat];
+                    player.alive = false;
+                    player.role = *role;
+                    player.hidden_cards = 0;
+                    // The shown cards include those in hand this view did not know, a view
+                    // not shown the cards still throws away what it knows the player had
+                    let left: Vec<CardId> = player
+                        .hand
+                        .cards()
+                        .iter()
+                        .chain(player.judgment.cards())
+                        .chain(player.equipment.cards())
+                        .map(|c| c.id)
+                        .filter(|id| !revealed.contains(id))
+                        .collect();
+                    let zones = [
+                        Zone::Hand(seat),
+                        Zone::Judgment(seat),
+                        Zone::Equipment(seat),
+                    ];
+                    for id in revealed.iter().chain(&left) {
+                        let card = zones.iter().find_map(|zone| self.take(*zone, *id));
+                        match card.or_else(|| database.card(*id)) {
+                            Some(card) => self.deck.discard(card),
+                            None => log::warn!("Unknown card {} of {} is lost", id, seat),
+                        }
+                    }
+                }
+            }
+            GameEvent::GameOver { roles, .. } => {
+                for (player, role) in roles {
+                    if let Some(seat) = self.seat_of(player) {
+                        self.players[seat].role = *role;
+                    }
+                }
+            }
+            GameEvent::SkillUsed { .. }
+            | GameEvent::Dying { .. }
+            | GameEvent::ResponseRequested { .. }
+            | GameEvent::DiscardRequested { .. }
+            | GameEvent::Responded { card: None, .. }
+            | GameEvent::Rescued { .. } => {}
+        }
+    }
+
+    /// Move a card between zones, with the card from the database if `from` does not have
+    /// it.
+    fn relocate(&mut self, database: &CardDatabase, id: CardId, from: Zone, to: Zone) {
+        let card = match self.take(from, id) {
+            Some(card) => Some(card),
+            None => {
+                // The card was one of those in hand this view did not know
+                if let Zone::Hand(seat) = from {
+                    let hidden = &mut self.players[seat].hidden_cards;
+                    *hidden = hidden.saturating_sub(1);
+                }
+                database.card(id)
+            }
+        };
+        match card {
+            Some(card) => self.put(to, card),
+            None => log::warn!("Unknown card {} cannot go from {:?} to {:?}", id, from, to),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn drawn() -> GameEvent {
+        GameEvent::CardsDrawn {
+            player: "alice".to_string(),
+            count: 2,
+            cards: vec![3, 4],
+        }
+    }
+
+    #[test]
+    fn players_see_their_own_draws() {
+        assert_eq!(drawn().view(Some("alice")), Some(drawn()));
+        let hidden = GameEvent::CardsDrawn {
+            player: "alice".to_string(),
+            count: 2,
+            cards: vec![],
+        };
+        assert_eq!(drawn().view(Some("bob")), Some(hidden));
+    }
+
+    #[test]
+    fn spectators_see_no_hands_or_hidden_roles() {
+        assert_eq!(drawn().view(None), None);
+
+        let given = GameEvent::CardGiven {
+            player: "alice".to_string(),
+            to: "bob".to_string(),
+            card: Some(7),
+        };
+        assert_eq!(given.view(None), None);
+
+        let rebel = GameEvent::RoleAssigned {
+            player: "alice".to_string(),
+            role: Role::Rebel,
+        };
+        assert_eq!(rebel.view(None), None);
+        let leader = GameEvent::RoleAssigned {
+            player: "bob".to_string(),
+            role: Role::Leader,
+        };
+        assert_eq!(leader.view(None), Some(leader.clone()));
+    }
+
+    #[test]
+    fn spectators_do_not_see_what_the_dead_reveal() {
+        let died = GameEvent::PlayerDied {
+            player: "alice".to_string(),
+            killer: Some("bob".to_string()),
+            role: Some(Role::Rebel),
+            revealed: vec![3, 4],
+        };
+        // Everybody at the table sees the role and the cards
+        assert_eq!(died.view(Some("bob")), Some(died.clone()));
+        let hidden = GameEvent::PlayerDied {
+            player: "alice".to_string(),
+            killer: Some("bob".to_string()),
+            role: None,
+            revealed: vec![],
+        };
+        assert_eq!(died.view(None), Some(hidden));
+    }
+
+    #[test]
+    fn the_dead_leave_nothing_behind_without_reveal() {
+        let database = CardDatabase::parse(
+            r#"(cards: [(name: "Swift Horse", category: Equipment(OffendHorse),
+                target: Myself, art: "", copies: [(Spade, 5)])])"#,
+        )
+        .unwrap();
+        let mut state = GameState::new(vec!["alice".to_string(), "bob".to_string()], vec![], 0);
+        let horse = database.card(0).unwrap();
+        state.equip(0, horse);
+        let died = GameEvent::PlayerDied {
+            player: "alice".to_string(),
+            killer: None,
+            role: None,
+            revealed: vec![],
+        };
+        state.apply(&died, &database);
+        assert_eq!(state.players[0].equipment.cards().count(), 0);
+        assert_eq!(state.deck.discard_pile.len(), 1);
+    }
+}
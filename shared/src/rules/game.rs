@@ -0,0 +1,118 @@
+use serde::{Deserialize, Serialize};
+
+use super::{
+    card::{Card, CardId},
+    deck::Deck,
+    player::{PlayerState, Seat},
+    turn::TurnState,
+    zone::{CardPile, Zone},
+};
+
+/// The complete state of a running game.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct GameState {
+    pub players: Vec<PlayerState>,
+    pub deck: Deck,
+    pub turn: TurnState,
+}
+
+impl GameState {
+    /// Seat the players in the given order, with all cards shuffled into the draw pile.
+    pub fn new(names: Vec<String>, cards: Vec<Card>, seed: u64) -> Self {
+        Self {
+            players: names
+                .into_iter()
+                .enumerate()
+                .map(|(seat, name)| PlayerState::new(seat, name))
+                .collect(),
+            deck: Deck::new(cards, seed),
+            turn: TurnState::new(0),
+        }
+    }
+
+    pub fn player(&self, seat: Seat) -> Option<&PlayerState> {
+        self.players.get(seat)
+    }
+
+    pub fn player_mut(&mut self, seat: Seat) -> Option<&mut PlayerState> {
+        self.players.get_mut(seat)
+    }
+
+    pub fn seat_of(&self, name: &str) -> Option<Seat> {
+        self.players.iter().position(|p| p.name == name)
+    }
+
+    /// For every seat whether its player is still in the game.
+    pub fn alive(&self) -> Vec<bool> {
+        self.players.iter().map(|p| p.alive).collect()
+    }
+
+    pub fn pile(&self, zone: Zone) -> Option<&CardPile> {
+        match zone {
+            Zone::DrawPile => Some(&self.deck.draw_pile),
+            Zone::DiscardPile => Some(&self.deck.discard_pile),
+            Zone::Hand(seat) => self.player(seat).map(|p| &p.hand),
+            Zone::Judgment(seat) => self.player(seat).map(|p| &p.judgment),
+            // Equipment is not a pile, see `PlayerState::equipment`
+            Zone::Equipment(_) => None,
+        }
+    }
+
+    fn pile_mut(&mut self, zone: Zone) -> Option<&mut CardPile> {
+        match zone {
+            Zone::DrawPile => Some(&mut self.deck.draw_pile),
+            Zone::DiscardPile => Some(&mut self.deck.discard_pile),
+            Zone::Hand(seat) => self.player_mut(seat).map(|p| &mut p.hand),
+            Zone::Judgment(seat) => self.player_mut(seat).map(|p| &mut p.judgment),
+            Zone::Equipment(_) => None,
+        }
+    }
+
+    /// Take a card out of a zone.
+    pub fn take(&mut self, zone: Zone, id: CardId) -> Option<Card> {
+        match zone {
+            Zone::Equipment(seat) => self.player_mut(seat)?.equipment.take(id),
+            zone => self.pile_mut(zone)?.take(id),
+        }
+    }
+
+    /// Put a card on top of a pile, or into its equipment slot, see `equip`.
+    pub fn put(&mut self, zone: Zone, card: Card) {
+        if let Zone::Equipment(seat) = zone {
+            self.equip(seat, card);
+            return;
+        }
+        match self.pile_mut(zone) {
+            Some(pile) => pile.push(card),
+            None => log::error!("Cannot put {} into {:?}", card, zone),
+        }
+    }
+
+    /// Put an equipment card into its slot in front of the player. A card already in the slot
+    /// is replaced and goes to the discard pile, its id is returned.
+    pub fn equip(&mut self, seat: Seat, card: Card) -> Option<CardId> {
+        let slot = match card.slot() {
+            Some(slot) => slot,
+            None => {
+                log::error!("{} is not an equipment card", card);
+                self.deck.discard(card);
+                return None;
+            }
+        };
+        let replaced = self.player_mut(seat)?.equipment.equip(slot, card)?;
+        let id = replaced.id;
+        self.deck.discard(replaced);
+        Some(id)
+    }
+
+    /// Move a card between zones. Fails if the card is not in `from`.
+    pub fn move_card(&mut self, id: CardId, from: Zone, to: Zone) -> bool {
+        match self.take(from, id) {
+            Some(card) => {
+                self.put(to, card);
+                true
+            }
+            None => false,
+        }
+    }
+}
@@ -0,0 +1,29 @@
+//! The rules of the card game, independent of any engine. The server runs them to decide
+//! what happens, the client runs the same code to predict and display the state.
+
+pub mod card;
+pub mod character;
+pub mod database;
+pub mod deck;
+pub mod engine;
+pub mod event;
+pub mod game;
+pub mod player;
+pub mod range;
+pub mod rng;
+pub mod role;
+pub mod turn;
+pub mod zone;
+
+pub use card::{Card, CardId, Category, EquipSlot, Suit};
+pub use character::{CharacterDefinition, SkillDefinition, SkillKind, Trigger};
+pub use database::{CardDatabase, CardDefinition, DatabaseError, TargetRule};
+pub use deck::Deck;
+pub use engine::{ActionError, Engine};
+pub use event::GameEvent;
+pub use game::GameState;
+pub use player::{PlayerState, Seat};
+pub use rng::Rng;
+pub use role::{GameMode, Role};
+pub use turn::{Phase, TurnState};
+pub use zone::{CardPile, Equipment, Zone};
@@ -0,0 +1,65 @@
+use serde::{Deserialize, Serialize};
+
+use super::{
+    role::Role,
+    zone::{CardPile, Equipment},
+};
+
+/// Position of a player at the table, counted from the first player.
+pub type Seat = usize;
+
+/// Health of a player whose character does not say otherwise.
+pub const DEFAULT_MAX_HEALTH: i32 = 4;
+
+/// Everything that belongs to a player in a running game.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct PlayerState {
+    pub seat: Seat,
+    pub name: String,
+    /// Name of the chosen character, see `CharacterDefinition`
+    pub character: Option<String>,
+    /// `None` in games without roles
+    pub role: Option<Role>,
+    pub hand: CardPile,
+    /// Cards in hand this view of the game does not know, such as the hands of the other
+    /// players on a client
+    pub hidden_cards: u32,
+    pub equipment: Equipment,
+    /// Delayed tricks waiting for the judgment phase, resolved last in first out
+    pub judgment: CardPile,
+    pub health: i32,
+    pub max_health: i32,
+    pub alive: bool,
+}
+
+impl PlayerState {
+    pub fn new(seat: Seat, name: String) -> Self {
+        Self {
+            seat,
+            name,
+            character: None,
+            role: None,
+            hand: CardPile::default(),
+            hidden_cards: 0,
+            equipment: Equipment::default(),
+            judgment: CardPile::default(),
+            health: DEFAULT_MAX_HEALTH,
+            max_health: DEFAULT_MAX_HEALTH,
+            alive: true,
+        }
+    }
+
+    /// How many cards the player holds, known or not.
+    pub fn hand_size(&self) -> usize {
+        self.hand.len() + self.hidden_cards as usize
+    }
+
+    /// Out of health but not dead yet, waiting to be rescued.
+    pub fn is_dying(&self) -> bool {
+        self.alive && self.health <= 0
+    }
+
+    pub fn is_wounded(&self) -> bool {
+        self.health < self.max_health
+    }
+}
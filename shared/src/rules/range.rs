@@ -0,0 +1,123 @@
+use super::{
+    card::{Card, Category},
+    database::{CardDatabase, TargetRule},
+    engine::ActionError,
+    game::GameState,
+    player::Seat,
+};
+
+/// The attack range without a weapon.
+pub const BASE_ATTACK_RANGE: u32 = 1;
+
+/// Distances around the table and who can be targeted with what. The server validates
+/// actions with these, the client uses the same to highlight possible targets.
+impl GameState {
+    /// Number of steps from one seat to another, going around the table the shorter way.
+    /// Dead players are skipped, they do not take up room at the table anymore.
+    pub fn seat_distance(&self, from: Seat, to: Seat) -> u32 {
+        let living: Vec<Seat> = self
+            .players
+            .iter()
+            .filter(|p| p.alive || p.seat == from || p.seat == to)
+            .map(|p| p.seat)
+            .collect();
+        let position = |seat| living.iter().position(|s| *s == seat);
+        match (position(from), position(to)) {
+            (Some(a), Some(b)) => {
+                let steps = a.max(b) - a.min(b);
+                steps.min(living.len() - steps) as u32
+            }
+            _ => 0,
+        }
+    }
+
+    /// The distance the rules use: the seat distance, one less if `from` has an offensive
+    /// horse, one more if `to` has a defensive horse. Skills with the same effect as a horse
+    /// count once more. Other players are at least 1 away.
+    pub fn distance(&self, database: &CardDatabase, from: Seat, to: Seat) -> u32 {
+        if from == to {
+            return 0;
+        }
+        let mut distance = self.seat_distance(from, to);
+        if self.players[from].equipment.offend_horse.is_some() {
+            distance = distance.saturating_sub(1);
+        }
+        if self.has_skill(database, from, "distance_to_others_minus_one") {
+            distance = distance.saturating_sub(1);
+        }
+        if self.players[to].equipment.defend_horse.is_some() {
+            distance += 1;
+        }
+        if self.has_skill(database, to, "distance_from_others_plus_one") {
+            distance += 1;
+        }
+        distance.max(1)
+    }
+
+    /// How far the player can attack, given by the weapon in front of the player and
+    /// extended by skills.
+    pub fn attack_range(&self, database: &CardDatabase, seat: Seat) -> u32 {
+        let range = self.players[seat]
+            .equipment
+            .weapon
+            .as_ref()
+            .and_then(|weapon| database.definition(weapon))
+            .and_then(|definition| definition.range)
+            .unwrap_or(BASE_ATTACK_RANGE);
+        if self.has_skill(database, seat, "attack_range_plus_one") {
+            range + 1
+        } else {
+            range
+        }
+    }
+
+    pub fn in_attack_range(&self, database: &CardDatabase, from: Seat, to: Seat) -> bool {
+        self.distance(database, from, to) <= self.attack_range(database, from)
+    }
+
+    /// Whether the player at `from` can aim the card at the player at `to`.
+    pub fn can_target(
+        &self,
+        database: &CardDatabase,
+        from: Seat,
+        to: Seat,
+        card: &Card,
+    ) -> Result<(), ActionError> {
+        let definition = database
+            .definition(card)
+            .ok_or(ActionError::NotPlayable(card.id))?;
+        let target = &self.players[to];
+        if !target.alive {
+            return Err(ActionError::UnknownTarget(target.name.clone()));
+        }
+        match definition.target {
+            TargetRule::Response => return Err(ActionError::NotPlayable(card.id)),
+            TargetRule::Myself if from != to => return Err(ActionError::InvalidTargets),
+            TargetRule::Other | TargetRule::OtherInAttackRange if from == to => {
+                return Err(ActionError::InvalidTargets)
+            }
+            TargetRule::OtherInAttackRange if !self.in_attack_range(database, from, to) => {
+                return Err(ActionError::OutOfRange(target.name.clone()))
+            }
+            _ => {}
+        }
+        // The range of equipment is what it gives to the player, not a limit of the target
+        let limit = match definition.category {
+            Category::Equipment(_) => None,
+            _ => definition.range,
+        };
+        match limit {
+            Some(range) if self.distance(database, from, to) > range => {
+                Err(ActionError::OutOfRange(target.name.clone()))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Every player the card can be aimed at, for highlighting them.
+    pub fn possible_targets(&self, database: &CardDatabase, from: Seat, card: &Card) -> Vec<Seat> {
+        (0..self.players.len())
+            .filter(|to| self.can_target(database, from, *to, card).is_ok())
+            .collect()
+    }
+}
@@ -0,0 +1,70 @@
+use serde::{Deserialize, Serialize};
+
+/// A small seeded random number generator (SplitMix64). It is part of the rules rather than
+/// an external crate, so a seed gives the same shuffles on every machine and every build,
+/// and a game can be played again exactly from its seed.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// A number in `0..bound`, `bound` must not be 0.
+    pub fn below(&mut self, bound: usize) -> usize {
+        ((u128::from(self.next_u64()) * bound as u128) >> 64) as usize
+    }
+
+    /// Fisher-Yates shuffle.
+    pub fn shuffle<T>(&mut self, items: &mut [T]) {
+        for i in (1..items.len()).rev() {
+            let j = self.below(i + 1);
+            items.swap(i, j);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shuffled(seed: u64) -> Vec<u32> {
+        let mut items: Vec<u32> = (0..52).collect();
+        Rng::new(seed).shuffle(&mut items);
+        items
+    }
+
+    #[test]
+    fn same_seed_gives_same_order() {
+        assert_eq!(shuffled(7), shuffled(7));
+    }
+
+    #[test]
+    fn different_seeds_give_different_orders() {
+        assert_ne!(shuffled(7), shuffled(8));
+    }
+
+    #[test]
+    fn shuffle_keeps_every_item() {
+        let mut items = shuffled(7);
+        items.sort_unstable();
+        assert_eq!(items, (0..52).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn below_stays_below_the_bound() {
+        let mut rng = Rng::new(7);
+        assert!((0..1000).all(|_| rng.below(3) < 3));
+    }
+}
@@ -0,0 +1,131 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt::{self, Display, Formatter},
+    str::FromStr,
+};
+
+use super::{game::GameState, player::Seat};
+
+/// The hidden identity of a player, only the leader is known to everybody.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Role {
+    Leader,
+    /// Wins together with the leader
+    Loyalist,
+    /// Wins once the leader is dead
+    Rebel,
+    /// Wins by being the last one left, the leader last
+    Renegade,
+}
+
+impl Role {
+    /// Whether everybody knows the role from the start.
+    pub fn is_revealed(self) -> bool {
+        self == Role::Leader
+    }
+}
+
+impl Display for Role {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+/// How a game is won, chosen with the rule set of the server.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GameMode {
+    /// Hidden roles, see `Role`.
+    Roles,
+    /// Everybody for themselves, the last player alive wins.
+    FreeForAll,
+    /// Two players, the one who stays alive wins.
+    Duel,
+}
+
+impl FromStr for GameMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "roles" => Ok(GameMode::Roles),
+            "ffa" => Ok(GameMode::FreeForAll),
+            "duel" => Ok(GameMode::Duel),
+            _ => Err(format!("Unknown mode {}, expected roles, ffa or duel", s)),
+        }
+    }
+}
+
+/// The roles of a game with the given number of players: one leader, about half of the
+/// others rebels, the rest loyalists and a renegade. Games without roles get none.
+pub fn roles(mode: GameMode, players: usize) -> Vec<Role> {
+    if mode != GameMode::Roles || players == 0 {
+        return vec![];
+    }
+    let (loyalists, rebels, renegades) = match players {
+        1 => (0, 0, 0),
+        2 => (0, 1, 0),
+        3 => (0, 1, 1),
+        4 => (1, 1, 1),
+        5 => (1, 2, 1),
+        6 => (1, 3, 1),
+        7 => (2, 3, 1),
+        n => (n - 6, 4, 1),
+    };
+    let mut roles = vec![Role::Leader];
+    roles.extend(vec![Role::Loyalist; loyalists]);
+    roles.extend(vec![Role::Rebel; rebels]);
+    roles.extend(vec![Role::Renegade; renegades]);
+    roles
+}
+
+impl GameState {
+    /// The seat of the leader, if the game has roles.
+    pub fn leader(&self) -> Option<Seat> {
+        self.players
+            .iter()
+            .position(|p| p.role == Some(Role::Leader))
+    }
+
+    /// The players who won, once the game is decided. Checked after every death.
+    pub fn winners(&self, mode: GameMode) -> Option<Vec<Seat>> {
+        let alive: Vec<Seat> = self
+            .players
+            .iter()
+            .filter(|p| p.alive)
+            .map(|p| p.seat)
+            .collect();
+        match mode {
+            GameMode::FreeForAll | GameMode::Duel => match alive.as_slice() {
+                [] => Some(vec![]),
+                [last] => Some(vec![*last]),
+                _ => None,
+            },
+            GameMode::Roles => {
+                let living = |role| alive.iter().any(|s| self.players[*s].role == Some(role));
+                let with_role = |role| {
+                    self.players
+                        .iter()
+                        .filter(|p| p.role == Some(role))
+                        .map(|p| p.seat)
+                        .collect::<Vec<Seat>>()
+                };
+                if !living(Role::Leader) {
+                    // The renegade needs to be the last one standing
+                    match alive.as_slice() {
+                        [last] if self.players[*last].role == Some(Role::Renegade) => {
+                            Some(vec![*last])
+                        }
+                        _ => Some(with_role(Role::Rebel)),
+                    }
+                } else if !living(Role::Rebel) && !living(Role::Renegade) {
+                    let mut winners = with_role(Role::Leader);
+                    winners.extend(with_role(Role::Loyalist));
+                    winners.sort();
+                    Some(winners)
+                } else {
+                    None
+                }
+            }
+        }
+    }
+}
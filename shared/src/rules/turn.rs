@@ -0,0 +1,75 @@
+use serde::{Deserialize, Serialize};
+
+use super::player::Seat;
+
+/// The phases of a turn, in order.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Phase {
+    Start,
+    /// Delayed tricks in front of the player are resolved.
+    Judgment,
+    /// The player draws cards.
+    Draw,
+    /// The player plays cards.
+    Play,
+    /// The player discards down to the hand limit.
+    Discard,
+    End,
+}
+
+impl Phase {
+    /// The phase after this one, `None` after the last phase of the turn.
+    pub fn next(self) -> Option<Phase> {
+        match self {
+            Phase::Start => Some(Phase::Judgment),
+            Phase::Judgment => Some(Phase::Draw),
+            Phase::Draw => Some(Phase::Play),
+            Phase::Play => Some(Phase::Discard),
+            Phase::Discard => Some(Phase::End),
+            Phase::End => None,
+        }
+    }
+}
+
+/// Whose turn it is and how far it went.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub struct TurnState {
+    /// Starts at 1 and grows whenever the first seat gets its turn again
+    pub round: u32,
+    pub current: Seat,
+    pub phase: Phase,
+}
+
+impl TurnState {
+    pub fn new(first: Seat) -> Self {
+        Self {
+            round: 1,
+            current: first,
+            phase: Phase::Start,
+        }
+    }
+
+    /// Go to the next phase, or to the start of the next living player's turn. `alive` tells
+    /// for every seat whether its player is still in the game.
+    pub fn advance(&mut self, alive: &[bool]) {
+        match self.phase.next() {
+            Some(phase) => self.phase = phase,
+            None => {
+                let next = next_seat(self.current, alive).unwrap_or(self.current);
+                if next <= self.current {
+                    self.round += 1;
+                }
+                self.current = next;
+                self.phase = Phase::Start;
+            }
+        }
+    }
+}
+
+/// The next living seat after `seat` in turn order, wrapping around the table.
+pub fn next_seat(seat: Seat, alive: &[bool]) -> Option<Seat> {
+    let count = alive.len();
+    (1..=count)
+        .map(|offset| (seat + offset) % count)
+        .find(|s| alive[*s])
+}
@@ -0,0 +1,113 @@
+use serde::{Deserialize, Serialize};
+
+use super::{
+    card::{Card, CardId, EquipSlot},
+    player::Seat,
+};
+
+/// Everywhere a card can be.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Zone {
+    DrawPile,
+    DiscardPile,
+    Hand(Seat),
+    Equipment(Seat),
+    /// Delayed tricks waiting in front of a player for the judgment phase.
+    Judgment(Seat),
+}
+
+/// An ordered stack of cards. The top is the end of the vector.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, Eq)]
+pub struct CardPile {
+    cards: Vec<Card>,
+}
+
+impl CardPile {
+    pub fn new(cards: Vec<Card>) -> Self {
+        Self { cards }
+    }
+
+    pub fn len(&self) -> usize {
+        self.cards.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cards.is_empty()
+    }
+
+    pub fn cards(&self) -> &[Card] {
+        &self.cards
+    }
+
+    pub fn push(&mut self, card: Card) {
+        self.cards.push(card);
+    }
+
+    /// Take the top card.
+    pub fn pop(&mut self) -> Option<Card> {
+        self.cards.pop()
+    }
+
+    pub fn contains(&self, id: CardId) -> bool {
+        self.cards.iter().any(|c| c.id == id)
+    }
+
+    /// Take the card with the given id out of the pile, wherever it is.
+    pub fn take(&mut self, id: CardId) -> Option<Card> {
+        let index = self.cards.iter().position(|c| c.id == id)?;
+        Some(self.cards.remove(index))
+    }
+
+    /// Take all cards, e.g. to shuffle them into another pile.
+    pub fn take_all(&mut self) -> Vec<Card> {
+        std::mem::take(&mut self.cards)
+    }
+}
+
+/// The equipment slots in front of a player.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, Eq)]
+pub struct Equipment {
+    pub weapon: Option<Card>,
+    pub armor: Option<Card>,
+    pub offend_horse: Option<Card>,
+    pub defend_horse: Option<Card>,
+}
+
+impl Equipment {
+    pub fn slot(&self, slot: EquipSlot) -> Option<&Card> {
+        match slot {
+            EquipSlot::Weapon => self.weapon.as_ref(),
+            EquipSlot::Armor => self.armor.as_ref(),
+            EquipSlot::OffendHorse => self.offend_horse.as_ref(),
+            EquipSlot::DefendHorse => self.defend_horse.as_ref(),
+        }
+    }
+
+    fn slot_mut(&mut self, slot: EquipSlot) -> &mut Option<Card> {
+        match slot {
+            EquipSlot::Weapon => &mut self.weapon,
+            EquipSlot::Armor => &mut self.armor,
+            EquipSlot::OffendHorse => &mut self.offend_horse,
+            EquipSlot::DefendHorse => &mut self.defend_horse,
+        }
+    }
+
+    /// Put a card into a slot and return the card it replaced.
+    pub fn equip(&mut self, slot: EquipSlot, card: Card) -> Option<Card> {
+        self.slot_mut(slot).replace(card)
+    }
+
+    /// Take the card with the given id out of its slot.
+    pub fn take(&mut self, id: CardId) -> Option<Card> {
+        let slot = self.cards().find(|c| c.id == id)?.slot()?;
+        self.slot_mut(slot).take()
+    }
+
+    pub fn cards(&self) -> impl Iterator<Item = &Card> {
+        self.weapon
+            .iter()
+            .chain(self.armor.iter())
+            .chain(self.offend_horse.iter())
+            .chain(self.defend_horse.iter())
+    }
+}
@@ -8,6 +8,8 @@ const UDP_PORT: u16 = 2000;
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub struct ClientInfo {
     pub name: String,
+    /// The local port of the client. It tells clients with the same name apart, but is not
+    /// used for routing: the server replies to the address the messages come from.
     pub port: u16,
 }
 
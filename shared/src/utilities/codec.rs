@@ -0,0 +1,91 @@
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::msg::{MessageError, Result};
+
+/// First byte of everything encoded by the `BinaryCodec`. JSON documents always start with `{`,
+/// so the receiver can tell the formats apart without knowing what the peer negotiated.
+const BINARY_MAGIC: u8 = 0xB1;
+
+/// The wire formats a peer can speak.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CodecKind {
+    /// Human readable, used for the handshake and for debugging.
+    Json,
+    /// Compact, used for everything else by default.
+    Binary,
+}
+
+impl CodecKind {
+    /// All formats, in order of preference.
+    pub const ALL: &'static [CodecKind] = &[CodecKind::Binary, CodecKind::Json];
+
+    /// Tell which codec produced the given bytes.
+    pub fn detect(bytes: &[u8]) -> Option<CodecKind> {
+        match bytes.first() {
+            Some(&BINARY_MAGIC) => Some(CodecKind::Binary),
+            Some(b'{') => Some(CodecKind::Json),
+            _ => None,
+        }
+    }
+
+    pub fn codec<T: Serialize + DeserializeOwned>(self) -> Box<dyn Codec<T>> {
+        match self {
+            CodecKind::Json => Box::new(JsonCodec),
+            CodecKind::Binary => Box::new(BinaryCodec),
+        }
+    }
+}
+
+/// Turns values into bytes on the wire and back.
+pub trait Codec<T>: Send + Sync {
+    fn kind(&self) -> CodecKind;
+    fn encode(&self, value: &T) -> Result<Vec<u8>>;
+    fn decode(&self, bytes: &[u8]) -> Result<T>;
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct JsonCodec;
+
+impl<T: Serialize + DeserializeOwned> Codec<T> for JsonCodec {
+    fn kind(&self) -> CodecKind {
+        CodecKind::Json
+    }
+
+    fn encode(&self, value: &T) -> Result<Vec<u8>> {
+        serde_json::to_vec(value).map_err(Into::into)
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Result<T> {
+        serde_json::from_slice(bytes).map_err(Into::into)
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct BinaryCodec;
+
+impl<T: Serialize + DeserializeOwned> Codec<T> for BinaryCodec {
+    fn kind(&self) -> CodecKind {
+        CodecKind::Binary
+    }
+
+    fn encode(&self, value: &T) -> Result<Vec<u8>> {
+        let mut bytes = vec![BINARY_MAGIC];
+        bincode::serialize_into(&mut bytes, value)?;
+        Ok(bytes)
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Result<T> {
+        match bytes.split_first() {
+            Some((&BINARY_MAGIC, body)) => bincode::deserialize(body).map_err(Into::into),
+            _ => Err(MessageError::UnknownFormat),
+        }
+    }
+}
+
+/// Decode bytes produced by any of the codecs.
+pub fn decode_any<T: Serialize + DeserializeOwned>(bytes: &[u8]) -> Result<T> {
+    CodecKind::detect(bytes)
+        .ok_or(MessageError::UnknownFormat)?
+        .codec()
+        .decode(bytes)
+}
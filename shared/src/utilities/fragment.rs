@@ -0,0 +1,235 @@
+use std::{
+    collections::HashMap,
+    convert::TryInto,
+    time::{Duration, Instant},
+};
+
+use crate::msg::{MessageError, Result};
+
+/// First byte of every fragment. It differs from the first byte of all codecs, so whole
+/// datagrams and fragments can be told apart.
+const FRAGMENT_MAGIC: u8 = 0xF7;
+/// Magic, message id, fragment index and fragment count.
+const HEADER_LEN: usize = 1 + 4 + 2 + 2;
+
+/// Largest datagram that is sent as is. Stays well below the receive buffers of 2048 bytes
+/// and the usual MTU.
+pub const MAX_DATAGRAM: usize = 1200;
+
+/// Split an encoded message into datagrams of at most `MAX_DATAGRAM` bytes.
+/// Small messages are returned unchanged.
+#[derive(Debug, Default)]
+pub struct Fragmenter {
+    next_id: u32,
+}
+
+impl Fragmenter {
+    pub fn split(&mut self, bytes: Vec<u8>) -> Result<Vec<Vec<u8>>> {
+        if bytes.len() <= MAX_DATAGRAM {
+            return Ok(vec![bytes]);
+        }
+        let chunks: Vec<&[u8]> = bytes.chunks(MAX_DATAGRAM - HEADER_LEN).collect();
+        if chunks.len() > u16::MAX as usize {
+            return Err(MessageError::TooLarge);
+        }
+        let id = self.next_id;
+        self.next_id = self.next_id.wrapping_add(1);
+        let count = chunks.len() as u16;
+        Ok(chunks
+            .into_iter()
+            .enumerate()
+            .map(|(index, chunk)| {
+                let mut datagram = Vec::with_capacity(HEADER_LEN + chunk.len());
+                datagram.push(FRAGMENT_MAGIC);
+                datagram.extend_from_slice(&id.to_be_bytes());
+                datagram.extend_from_slice(&(index as u16).to_be_bytes());
+                datagram.extend_from_slice(&count.to_be_bytes());
+                datagram.extend_from_slice(chunk);
+                datagram
+            })
+            .collect())
+    }
+}
+
+#[derive(Debug)]
+struct Partial {
+    fragments: Vec<Option<Vec<u8>>>,
+    missing: usize,
+    started: Instant,
+}
+
+/// Collects fragments until a message is complete. Messages whose fragments do not all
+/// arrive in time are dropped, the reliability layer sends them again as a whole.
+#[derive(Debug)]
+pub struct Reassembler {
+    timeout: Duration,
+    partial: HashMap<u32, Partial>,
+}
+
+impl Default for Reassembler {
+    fn default() -> Self {
+        Self::new(Duration::from_secs(5))
+    }
+}
+
+impl Reassembler {
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            timeout,
+            partial: HashMap::default(),
+        }
+    }
+
+    /// Whether the datagram is a fragment at all.
+    pub fn is_fragment(datagram: &[u8]) -> bool {
+        datagram.first() == Some(&FRAGMENT_MAGIC)
+    }
+
+    /// Add a fragment and return the whole message once its last fragment arrived.
+    pub fn insert(&mut self, datagram: &[u8], now: Instant) -> Result<Option<Vec<u8>>> {
+        if datagram.len() < HEADER_LEN || !Self::is_fragment(datagram) {
+            return Err(MessageError::UnknownFormat);
+        }
+        let id = u32::from_be_bytes(datagram[1..5].try_into().unwrap());
+        let index = u16::from_be_bytes(datagram[5..7].try_into().unwrap()) as usize;
+        let count = u16::from_be_bytes(datagram[7..9].try_into().unwrap()) as usize;
+        if index >= count {
+            return Err(MessageError::UnknownFormat);
+        }
+
+        let partial = self.partial.entry(id).or_insert_with(|| Partial {
+            fragments: vec![None; count],
+            missing: count,
+            started: now,
+        });
+        if partial.fragments.len() != count {
+            // Fragments of two different messages with the same id
+            self.partial.remove(&id);
+            return Err(MessageError::Incomplete);
+        }
+        if partial.fragments[index].is_none() {
+            partial.fragments[index] = Some(datagram[HEADER_LEN..].to_vec());
+            partial.missing -= 1;
+        }
+        if partial.missing > 0 {
+            return Ok(None);
+        }
+        let partial = self.partial.remove(&id).unwrap();
+        Ok(Some(
+            partial.fragments.into_iter().flatten().flatten().collect(),
+        ))
+    }
+
+    /// Drop the messages that were not completed in time. Fails if there were any.
+    pub fn expire(&mut self, now: Instant) -> Result<()> {
+        let timeout = self.timeout;
+        let before = self.partial.len();
+        self.partial
+            .retain(|_, p| now.duration_since(p.started) < timeout);
+        if self.partial.len() < before {
+            Err(MessageError::Incomplete)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i % 251) as u8).collect()
+    }
+
+    #[test]
+    fn small_messages_are_not_split() {
+        let bytes = message(MAX_DATAGRAM);
+        assert_eq!(Fragmenter::default().split(bytes.clone()).unwrap(), [bytes]);
+    }
+
+    #[test]
+    fn fragments_fit_into_a_datagram() {
+        let fragments = Fragmenter::default().split(message(5000)).unwrap();
+        assert_eq!(fragments.len(), 5);
+        assert!(fragments.iter().all(|f| f.len() <= MAX_DATAGRAM));
+        assert!(fragments.iter().all(|f| Reassembler::is_fragment(f)));
+    }
+
+    #[test]
+    fn fragments_are_reassembled_in_any_order() {
+        let now = Instant::now();
+        let bytes = message(5000);
+        let mut fragments = Fragmenter::default().split(bytes.clone()).unwrap();
+        fragments.reverse();
+        let last = fragments.pop().unwrap();
+
+        let mut reassembler = Reassembler::default();
+        for fragment in &fragments {
+            assert_eq!(reassembler.insert(fragment, now).unwrap(), None);
+        }
+        // A duplicate does not count twice
+        assert_eq!(reassembler.insert(&fragments[0], now).unwrap(), None);
+        assert_eq!(reassembler.insert(&last, now).unwrap(), Some(bytes));
+    }
+
+    #[test]
+    fn messages_are_told_apart() {
+        let now = Instant::now();
+        let mut fragmenter = Fragmenter::default();
+        let first = fragmenter.split(message(3000)).unwrap();
+        let second = fragmenter.split(message(2000)).unwrap();
+
+        let mut reassembler = Reassembler::default();
+        assert_eq!(reassembler.insert(&first[0], now).unwrap(), None);
+        assert_eq!(reassembler.insert(&second[0], now).unwrap(), None);
+        assert_eq!(reassembler.insert(&first[1], now).unwrap(), None);
+        assert_eq!(
+            reassembler.insert(&second[1], now).unwrap(),
+            Some(message(2000))
+        );
+        assert_eq!(
+            reassembler.insert(&first[2], now).unwrap(),
+            Some(message(3000))
+        );
+    }
+
+    #[test]
+    fn incomplete_messages_expire() {
+        let now = Instant::now();
+        let timeout = Duration::from_secs(5);
+        let fragments = Fragmenter::default().split(message(3000)).unwrap();
+        let mut reassembler = Reassembler::new(timeout);
+        reassembler.insert(&fragments[0], now).unwrap();
+
+        assert!(reassembler.expire(now + timeout / 2).is_ok());
+        assert!(matches!(
+            reassembler.expire(now + timeout),
+            Err(MessageError::Incomplete)
+        ));
+        // The rest of the message starts a new one, which misses the first fragment
+        let later = now + timeout;
+        assert_eq!(reassembler.insert(&fragments[1], later).unwrap(), None);
+        assert_eq!(reassembler.insert(&fragments[2], later).unwrap(), None);
+    }
+
+    #[test]
+    fn malformed_fragments_are_rejected() {
+        let now = Instant::now();
+        let mut reassembler = Reassembler::default();
+        assert!(matches!(
+            reassembler.insert(&[FRAGMENT_MAGIC, 0, 0], now),
+            Err(MessageError::UnknownFormat)
+        ));
+        let mut fragment = Fragmenter::default()
+            .split(message(3000))
+            .unwrap()
+            .remove(0);
+        // Index 3 of 3 fragments
+        fragment[5..9].copy_from_slice(&[0, 3, 0, 3]);
+        assert!(matches!(
+            reassembler.insert(&fragment, now),
+            Err(MessageError::UnknownFormat)
+        ));
+    }
+}
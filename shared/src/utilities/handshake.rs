@@ -0,0 +1,249 @@
+use serde::{Deserialize, Serialize};
+use std::fmt::{self, Display, Formatter};
+
+use crate::{codec::CodecKind, heartbeat::HeartbeatConfig, rules::GameMode};
+
+/// Version of the wire protocol. Bump it whenever the shape of `TransMessage` changes,
+/// so that old clients are rejected instead of failing to decode messages.
+pub const PROTOCOL_VERSION: u32 = 20;
+
+/// Optional features a peer supports.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Capability {
+    /// The peer can send and display chat messages.
+    Chat,
+    /// The peer can take a seat and play games.
+    Game,
+}
+
+/// Identifies the seat of a player on the server, so it can be taken again after the
+/// connection was lost.
+pub type SessionToken = u64;
+
+/// The capabilities every client must support to join this server.
+pub const REQUIRED_CAPABILITIES: &[Capability] = &[Capability::Chat, Capability::Game];
+
+/// The first message a client sends to the server. The handshake itself is always sent as JSON.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct Hello {
+    pub version: u32,
+    pub capabilities: Vec<Capability>,
+    /// Wire formats the client can speak, in order of preference
+    pub codecs: Vec<CodecKind>,
+    /// The session of an earlier connection the client wants to continue
+    pub resume: Option<SessionToken>,
+}
+
+impl Hello {
+    pub fn new(capabilities: Vec<Capability>, codecs: Vec<CodecKind>) -> Self {
+        Self {
+            version: PROTOCOL_VERSION,
+            capabilities,
+            codecs,
+            resume: None,
+        }
+    }
+
+    /// Pick the wire format for the rest of the session. JSON is used if the server
+    /// runs in debug mode or the client offers nothing else.
+    pub fn select_codec(&self, debug_wire: bool) -> CodecKind {
+        if debug_wire {
+            return CodecKind::Json;
+        }
+        self.codecs
+            .iter()
+            .copied()
+            .find(|c| CodecKind::ALL.contains(c))
+            .unwrap_or(CodecKind::Json)
+    }
+
+    /// Check whether the server can talk to the client which sent this hello.
+    pub fn verify(&self) -> Result<(), RejectReason> {
+        if self.version != PROTOCOL_VERSION {
+            return Err(RejectReason::VersionMismatch {
+                server: PROTOCOL_VERSION,
+                client: Some(self.version),
+            });
+        }
+        match REQUIRED_CAPABILITIES
+            .iter()
+            .find(|c| !self.capabilities.contains(c))
+        {
+            Some(missing) => Err(RejectReason::MissingCapability(*missing)),
+            None => Ok(()),
+        }
+    }
+
+    /// Best effort extraction of the protocol version from a hello which could not be
+    /// decoded, e.g. because it was sent by an older or newer client. The hello may come
+    /// reliably or, over tcp, as a lossy packet.
+    pub fn probe_version(payload: &[u8]) -> Option<u32> {
+        let value = serde_json::from_slice::<serde_json::Value>(payload).ok()?;
+        [
+            "/kind/Reliable/msg/Connection/body/Hello/version",
+            "/kind/Lossy/Connection/body/Hello/version",
+        ]
+        .iter()
+        .find_map(|path| value.pointer(path))
+        .and_then(|v| v.as_u64())
+        .map(|v| v as u32)
+    }
+}
+
+/// Most players a table has room for.
+pub const MAX_SEATS: usize = 8;
+
+/// The rules the server is running its games with.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct RuleSet {
+    pub name: String,
+    /// A game starts once this many players in the lobby are prepared
+    pub min_players: usize,
+    /// Number of seats at the table, at most `MAX_SEATS`
+    pub max_players: usize,
+    /// Seconds a player has to respond before passing
+    pub response_timeout: u32,
+    /// Seconds a player has for the play phase before it ends
+    pub turn_timeout: u32,
+    /// Seconds each player may go over the time limits during a game, none with 0
+    pub time_bank: u32,
+    pub mode: GameMode,
+}
+
+impl RuleSet {
+    /// The rules that go with a game mode.
+    pub fn preset(mode: GameMode) -> Self {
+        match mode {
+            GameMode::Roles => Self::default(),
+            GameMode::FreeForAll => Self {
+                name: "free for all".to_string(),
+                mode,
+                ..Self::default()
+            },
+            GameMode::Duel => Self {
+                name: "duel".to_string(),
+                min_players: 2,
+                max_players: 2,
+                mode,
+                ..Self::default()
+            },
+        }
+    }
+}
+
+impl Default for RuleSet {
+    fn default() -> Self {
+        Self {
+            name: "standard".to_string(),
+            min_players: 2,
+            max_players: MAX_SEATS,
+            response_timeout: 15,
+            turn_timeout: 60,
+            time_bank: 0,
+            mode: GameMode::Roles,
+        }
+    }
+}
+
+/// The server's answer to an accepted `Hello`.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct Welcome {
+    pub server_name: String,
+    pub rules: RuleSet,
+    /// Capabilities both sides support
+    pub capabilities: Vec<Capability>,
+    /// The wire format both sides use after the handshake
+    pub codec: CodecKind,
+    /// The client must show signs of life according to this
+    pub heartbeat: HeartbeatConfig,
+    /// Send this in the `Hello` of a later connection to take the seat again
+    pub session: SessionToken,
+    /// Whether the client got its old seat back. It does not enter the lobby again then,
+    /// the server sends a `Resync` and a `GameMessage::Replay` instead.
+    pub resumed: bool,
+}
+
+/// Why the server refused a client.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub enum RejectReason {
+    /// `client` is `None` if the version could not be read from the hello at all.
+    VersionMismatch { server: u32, client: Option<u32> },
+    MissingCapability(Capability),
+    LobbyFull,
+    /// Another player in the lobby goes by the same name, players are told apart by it.
+    NameTaken(String),
+}
+
+impl Display for RejectReason {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            RejectReason::VersionMismatch {
+                server,
+                client: Some(client),
+            } => write!(
+                f,
+                "Protocol version {} is not supported, the server speaks version {}",
+                client, server
+            ),
+            RejectReason::VersionMismatch {
+                server,
+                client: None,
+            } => write!(
+                f,
+                "Unknown protocol version, the server speaks version {}",
+                server
+            ),
+            RejectReason::MissingCapability(c) => {
+                write!(f, "The client does not support {:?}", c)
+            }
+            RejectReason::LobbyFull => write!(f, "The lobby is full"),
+            RejectReason::NameTaken(name) => {
+                write!(f, "Somebody in the lobby is called {} already", name)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        clientinfo::ClientInfo,
+        msg::{ConnectionMessage, TransMessage},
+        reliable::{Packet, PacketKind},
+    };
+
+    fn hello(version: u32) -> TransMessage {
+        let hello = Hello {
+            version,
+            ..Hello::new(vec![], vec![])
+        };
+        TransMessage::connection(ClientInfo::default(), ConnectionMessage::Hello(hello))
+    }
+
+    fn probe(kind: PacketKind) -> Option<u32> {
+        let packet = Packet { session: 1, kind };
+        Hello::probe_version(&serde_json::to_vec(&packet).unwrap())
+    }
+
+    #[test]
+    fn version_of_a_reliable_hello() {
+        let kind = PacketKind::Reliable {
+            channel: hello(0).layer(),
+            seq: 0,
+            msg: hello(99),
+        };
+        assert_eq!(probe(kind), Some(99));
+    }
+
+    #[test]
+    fn version_of_a_lossy_hello() {
+        assert_eq!(probe(PacketKind::Lossy(hello(99))), Some(99));
+    }
+
+    #[test]
+    fn no_version_in_garbage() {
+        assert_eq!(Hello::probe_version(b"{\"kind\": 3}"), None);
+        assert_eq!(Hello::probe_version(&[0xF7, 1, 2]), None);
+    }
+}
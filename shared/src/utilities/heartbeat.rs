@@ -0,0 +1,77 @@
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+/// How often both sides show that they are still there, and how long they wait for the
+/// other side before giving up on it. The server tells the clients its values in the `Welcome`.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub struct HeartbeatConfig {
+    /// Time between two heartbeats
+    pub interval: Duration,
+    /// Consider the remote side gone if nothing arrived for this long
+    pub timeout: Duration,
+}
+
+impl Default for HeartbeatConfig {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(1),
+            timeout: Duration::from_secs(10),
+        }
+    }
+}
+
+/// Tracks when the remote side was last heard of, and when it was last sent anything. Every
+/// message shows the remote side that we are still there, a heartbeat is only needed when
+/// nothing else was sent for an interval.
+#[derive(Debug, Clone, Copy)]
+pub struct Liveness {
+    config: HeartbeatConfig,
+    last_seen: Instant,
+    last_sent: Instant,
+}
+
+impl Liveness {
+    pub fn new(config: HeartbeatConfig, now: Instant) -> Self {
+        Self {
+            config,
+            last_seen: now,
+            last_sent: now,
+        }
+    }
+
+    /// Something arrived from the remote side.
+    pub fn seen(&mut self, now: Instant) {
+        self.last_seen = now;
+    }
+
+    /// Something was sent to the remote side, a heartbeat or any other message.
+    pub fn sent(&mut self, now: Instant) {
+        self.last_sent = now;
+    }
+
+    /// Whether nothing was sent to the remote side for an interval.
+    pub fn heartbeat_due(&self, now: Instant) -> bool {
+        now.duration_since(self.last_sent) >= self.config.interval
+    }
+
+    pub fn timed_out(&self, now: Instant) -> bool {
+        now.duration_since(self.last_seen) >= self.config.timeout
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn traffic_postpones_the_heartbeat() {
+        let start = Instant::now();
+        let config = HeartbeatConfig::default();
+        let mut liveness = Liveness::new(config, start);
+        assert!(!liveness.heartbeat_due(start));
+        let half = config.interval / 2;
+        liveness.sent(start + half);
+        assert!(!liveness.heartbeat_due(start + config.interval));
+        assert!(liveness.heartbeat_due(start + half + config.interval));
+    }
+}
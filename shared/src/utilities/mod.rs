@@ -1,2 +1,9 @@
 pub mod clientinfo;
+pub mod codec;
+pub mod fragment;
+pub mod handshake;
+pub mod heartbeat;
 pub mod msg;
+pub mod peer;
+pub mod reliable;
+pub mod transport;
@@ -1,9 +1,13 @@
 use serde::{Deserialize, Serialize};
 use std::fmt::{self, Display, Formatter};
 
-use crate::clientinfo::ClientInfo;
+use crate::{
+    clientinfo::ClientInfo,
+    handshake::{Hello, RejectReason, Welcome},
+    rules::{ActionError, CardId, GameEvent},
+};
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum MessageLayer {
     // information in info box
     System,
@@ -19,45 +23,174 @@ pub enum MessageLayer {
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub enum TransMessage {
-    Connection(Message),
-    System(Message),
-    Lobby(Message),
-    Chat(Message),
-    Game(Message),
+    Connection(Message<ConnectionMessage>),
+    System(Message<SystemMessage>),
+    Lobby(Message<LobbyMessage>),
+    Chat(Message<ChatMessage>),
+    Game(Message<GameMessage>),
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
-pub struct Message {
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct Message<T> {
     pub from: ClientInfo,
-    /// Message content
-    /// NOTE: For more complex message content, consider use the MessageBody structure
-    pub msg_type: MessageType,
-    pub msg: String,
+    /// Typed message content, see the payload enums of each layer below
+    pub body: T,
 }
 
+/// Payload of the `Connection` layer.
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
-pub enum MessageType {
-    Exit,
+pub enum ConnectionMessage {
+    /// Client -> Server: the first message of a client, see `handshake`.
+    Hello(Hello),
+    /// Server -> Client: the hello was accepted.
+    Accepted(Welcome),
+    /// Server -> Client: the hello was refused, the client should not send anything else.
+    Rejected(RejectReason),
+    /// Client -> Server: the sender wants to enter the game lobby.
     EnterLobby,
+    /// Client -> Server: the sender leaves the server.
+    Exit,
+    /// Server -> Client: the given player is in the game lobby.
+    PlayerEntered(ClientInfo),
+    /// Server -> Client: the given player has left the server.
+    PlayerExited(ClientInfo),
+    /// Server -> Client: the given player stopped answering and was removed.
+    PlayerTimedOut(ClientInfo),
+    /// Both directions: the sender is still there, sent when there is nothing else to send.
+    Heartbeat,
+    /// Server -> Client: everything a client that got its seat back has missed.
+    Resync(Resync),
+}
+
+/// The state of the server a resumed client starts over with.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct Resync {
+    /// Everybody who has a seat, including the receiver
+    pub players: Vec<ClientInfo>,
+    /// The latest chat messages, oldest first
+    pub chat: Vec<Message<ChatMessage>>,
+    /// Who sits where, see `LobbyMessage::Seats`
+    #[serde(default)]
+    pub seats: Vec<Option<String>>,
+}
+
+/// Payload of the `System` layer, shown in the info box.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub enum SystemMessage {
+    Notice(String),
+}
+
+/// Payload of the `Lobby` layer.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub enum LobbyMessage {
+    /// Client -> Server: the sender is ready to start the game.
     Prepare,
+    /// Client -> Server: the sender is no longer ready.
     CancelPrepare,
-    Chat,
+    /// Server -> Client: the readiness of a player changed.
+    PlayerPrepared { player: ClientInfo, prepared: bool },
+    /// Client -> Server: the sender plays the next game with this character, or with none.
+    ChooseCharacter { character: Option<String> },
+    /// Server -> Client: a player chose a character.
+    CharacterChosen {
+        player: ClientInfo,
+        character: Option<String>,
+    },
+    /// Client -> Server: the sender wants to sit on `seat`, whoever sits there gets the
+    /// seat of the sender.
+    ChooseSeat { seat: usize },
+    /// Server -> Client: the name of the player on every seat of the table, in turn order.
+    Seats { seats: Vec<Option<String>> },
 }
-// #[derive(Debug, Serialize, Deserialize)]
-// pub struct MessageBody {
-//     // layer: MessageLayer,
-//     // msg: String,
-// }
 
-// #[derive(Debug, Serialize, Deserialize)]
-// pub enum MessageLayer {
-//     Chat,
-// }
+/// Payload of the `Chat` layer.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct ChatMessage {
+    pub text: String,
+}
+
+/// Payload of the `Game` layer.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub enum GameMessage {
+    /// Client -> Server: something the player wants to do.
+    Action(GameAction),
+    /// Server -> Client: something that happened at the table, the `seq`th event of the
+    /// game counting from zero.
+    Event { seq: u64, event: GameEvent },
+    /// Server -> Client: what the receiver may see of the events of the running game so far,
+    /// to rebuild the view of it after taking the seat again. `seq` is the number of the
+    /// last one, the events that follow are numbered after it. Sent on this layer so it
+    /// arrives in order with them.
+    Replay {
+        seq: Option<u64>,
+        events: Vec<GameEvent>,
+    },
+    /// Server -> Client: the last action of the receiver was not carried out.
+    Rejected(ActionError),
+    /// Server -> Client: the receiver is asked to respond within `timeout` seconds, or
+    /// passes.
+    Prompt { prompt: Prompt, timeout: u32 },
+    /// Server -> Client: `player` has `seconds` left to act, out of the time bank of the
+    /// player if `bank`. Sent every second while the game waits for somebody.
+    Timer {
+        player: String,
+        seconds: u32,
+        bank: bool,
+    },
+}
+
+/// What a player is asked to respond to. Cancels and rescues are answered with
+/// `GameAction::Respond`, and passing is always allowed. The discard is answered with
+/// `GameAction::Discard`.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub enum Prompt {
+    /// Cancel `card`, which `source` aimed at `target`, with a card that has the `answer`
+    /// effect.
+    Cancel {
+        card: CardId,
+        source: String,
+        target: String,
+        answer: String,
+    },
+    /// Save the dying player with a card that heals.
+    Rescue { dying: String },
+    /// Discard `count` of the cards in `hand` to get down to the hand limit. Without an
+    /// answer the cards drawn last are discarded.
+    Discard { count: u32, hand: Vec<CardId> },
+}
+
+/// The actions a player can take during a game. Players are identified by name.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub enum GameAction {
+    /// Play a card from hand, optionally aimed at some players.
+    PlayCard { card: CardId, targets: Vec<String> },
+    /// Answer another player's action, `None` means to pass.
+    Respond { card: Option<CardId> },
+    /// Throw away the given cards from hand.
+    Discard { cards: Vec<CardId> },
+    /// Use an active skill of the own character with the chosen cards and players.
+    UseSkill {
+        skill: String,
+        cards: Vec<CardId>,
+        targets: Vec<String>,
+    },
+    /// Finish the current phase of the own turn.
+    EndPhase,
+}
 
 #[derive(Debug, Serialize, Deserialize)]
 pub enum MessageError {
     FromBytesError,
     SerdeJsonError,
+    BincodeError,
+    // The bytes were not produced by any known codec
+    UnknownFormat,
+    // A reliable message was never acknowledged by the remote side
+    Unacknowledged,
+    // Not all fragments of a message arrived in time
+    Incomplete,
+    // The message is too big to be split into fragments
+    TooLarge,
 }
 
 impl From<serde_json::Error> for MessageError {
@@ -66,28 +199,34 @@ impl From<serde_json::Error> for MessageError {
     }
 }
 
+impl From<bincode::Error> for MessageError {
+    fn from(_: bincode::Error) -> Self {
+        MessageError::BincodeError
+    }
+}
+
 pub type Error = MessageError;
 pub type Result<T> = std::result::Result<T, MessageError>;
 
 impl TransMessage {
-    pub fn new(
-        layer: MessageLayer,
-        from: ClientInfo,
-        msg_type: MessageType,
-        msg: String,
-    ) -> TransMessage {
-        let msg = Message::new(from, msg_type, msg);
-        TransMessage::construct(layer, msg)
+    pub fn connection(from: ClientInfo, body: ConnectionMessage) -> TransMessage {
+        TransMessage::Connection(Message::new(from, body))
     }
 
-    pub fn construct(layer: MessageLayer, m: Message) -> TransMessage {
-        match layer {
-            MessageLayer::System => TransMessage::System(m),
-            MessageLayer::Connection => TransMessage::Connection(m),
-            MessageLayer::Chat => TransMessage::Chat(m),
-            MessageLayer::Lobby => TransMessage::Lobby(m),
-            MessageLayer::Game => TransMessage::Game(m),
-        }
+    pub fn system(from: ClientInfo, body: SystemMessage) -> TransMessage {
+        TransMessage::System(Message::new(from, body))
+    }
+
+    pub fn lobby(from: ClientInfo, body: LobbyMessage) -> TransMessage {
+        TransMessage::Lobby(Message::new(from, body))
+    }
+
+    pub fn chat(from: ClientInfo, text: String) -> TransMessage {
+        TransMessage::Chat(Message::new(from, ChatMessage { text }))
+    }
+
+    pub fn game(from: ClientInfo, body: GameMessage) -> TransMessage {
+        TransMessage::Game(Message::new(from, body))
     }
 
     pub fn serialize(&self) -> Result<String> {
@@ -95,28 +234,31 @@ impl TransMessage {
         serde_json::to_string(&self).map_err::<Error, _>(Into::into)
     }
 
-    pub fn update_layer(&self, layer: MessageLayer) -> Self {
-        let m = match self {
-            TransMessage::System(m) => m,
-            TransMessage::Connection(m) => m,
-            TransMessage::Chat(m) => m,
-            TransMessage::Lobby(m) => m,
-            TransMessage::Game(m) => m,
-        };
-
-        // Fixed msg, but change layer
-        TransMessage::construct(layer, m.clone())
+    pub fn layer(&self) -> MessageLayer {
+        match self {
+            TransMessage::System(_) => MessageLayer::System,
+            TransMessage::Connection(_) => MessageLayer::Connection,
+            TransMessage::Chat(_) => MessageLayer::Chat,
+            TransMessage::Lobby(_) => MessageLayer::Lobby,
+            TransMessage::Game(_) => MessageLayer::Game,
+        }
     }
-}
 
-impl Message {
-    pub fn new(from: ClientInfo, msg_type: MessageType, msg: String) -> Message {
-        Message {
-            from,
-            msg_type,
-            msg,
+    pub fn sender(&self) -> &ClientInfo {
+        match self {
+            TransMessage::System(m) => &m.from,
+            TransMessage::Connection(m) => &m.from,
+            TransMessage::Chat(m) => &m.from,
+            TransMessage::Lobby(m) => &m.from,
+            TransMessage::Game(m) => &m.from,
         }
     }
+}
+
+impl<T> Message<T> {
+    pub fn new(from: ClientInfo, body: T) -> Message<T> {
+        Message { from, body }
+    }
 
     // pub fn from_bytes(bytes: Bytes) -> Result<Message> {
     //     // Converting messages to human-readable form
@@ -140,11 +282,11 @@ impl Message {
 impl Display for TransMessage {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
         match self {
-            TransMessage::System(m) => write!(f, "Default [{}]: {}", m.from, m.msg),
-            TransMessage::Connection(m) => write!(f, "S2C [{}]: {}", m.from, m.msg),
-            TransMessage::Chat(m) => write!(f, "Ord [{}]: {}", m.from, m.msg),
-            TransMessage::Lobby(m) => write!(f, "ConnReq [{}]: {}", m.from, m.msg),
-            TransMessage::Game(m) => write!(f, "C2S [{}]: {}", m.from, m.msg),
+            TransMessage::System(m) => write!(f, "System [{}]: {:?}", m.from, m.body),
+            TransMessage::Connection(m) => write!(f, "Connection [{}]: {:?}", m.from, m.body),
+            TransMessage::Chat(m) => write!(f, "Chat [{}]: {}", m.from, m.body.text),
+            TransMessage::Lobby(m) => write!(f, "Lobby [{}]: {:?}", m.from, m.body),
+            TransMessage::Game(m) => write!(f, "Game [{}]: {:?}", m.from, m.body),
         }
     }
 }
@@ -0,0 +1,185 @@
+use log::error;
+use std::time::Instant;
+
+use crate::{
+    codec::{decode_any, Codec, CodecKind},
+    fragment::{Fragmenter, Reassembler},
+    msg::{Result, TransMessage},
+    reliable::{Delivery, Packet, ReliableEndpoint},
+    transport::{frame, FrameDecoder, Transport},
+};
+
+/// One end of a conversation between a client and the server. Messages are run through the
+/// reliability layer and encoded with the negotiated codec. Over udp they are split into
+/// fragments if they do not fit into a single datagram, over tcp every message becomes a
+/// length-prefixed frame. Like the `ReliableEndpoint` it does not do any io itself, the
+/// bytes to send are picked up with `drain_outgoing`.
+pub struct Peer {
+    codec: Box<dyn Codec<Packet>>,
+    transport: Transport,
+    endpoint: ReliableEndpoint,
+    fragmenter: Fragmenter,
+    reassembler: Reassembler,
+    frames: FrameDecoder,
+}
+
+impl Peer {
+    pub fn new(codec: CodecKind, transport: Transport) -> Self {
+        Self {
+            codec: codec.codec(),
+            transport,
+            endpoint: ReliableEndpoint::default(),
+            fragmenter: Fragmenter::default(),
+            reassembler: Reassembler::default(),
+            frames: FrameDecoder::default(),
+        }
+    }
+
+    pub fn codec(&self) -> CodecKind {
+        self.codec.kind()
+    }
+
+    pub fn transport(&self) -> Transport {
+        self.transport
+    }
+
+    /// Switch the wire format, e.g. after the handshake. Incoming datagrams are decoded
+    /// regardless of the format they were sent in.
+    pub fn set_codec(&mut self, codec: CodecKind) {
+        self.codec = codec.codec();
+    }
+
+    pub fn send(&mut self, msg: TransMessage, delivery: Delivery, now: Instant) {
+        let delivery = match self.transport {
+            Transport::Udp => delivery,
+            // The stream already delivers everything in order, acks would only add traffic
+            Transport::Tcp => Delivery::Lossy,
+        };
+        self.endpoint.send(msg, delivery, now);
+    }
+
+    /// Decode a datagram, or a chunk read from the stream, and add the messages that are
+    /// ready to be handled to `messages`, in order, also those decoded before an error. Over
+    /// tcp a frame that cannot be decoded is skipped, an error in the framing itself breaks
+    /// the stream, see `is_broken`.
+    pub fn receive(
+        &mut self,
+        bytes: &[u8],
+        now: Instant,
+        messages: &mut Vec<TransMessage>,
+    ) -> Result<()> {
+        match self.transport {
+            Transport::Udp => {
+                messages.extend(self.receive_datagram(bytes, now)?);
+                Ok(())
+            }
+            Transport::Tcp => {
+                self.frames.extend(bytes);
+                let mut result = Ok(());
+                loop {
+                    let frame = match self.frames.next_frame() {
+                        Ok(Some(frame)) => frame,
+                        Ok(None) => break,
+                        Err(e) => return Err(e),
+                    };
+                    match decode_any::<Packet>(&frame) {
+                        Ok(packet) => messages.extend(self.endpoint.receive(packet, now)),
+                        Err(e) => result = result.and(Err(e)),
+                    }
+                }
+                result
+            }
+        }
+    }
+
+    /// Whether the stream from the remote side is corrupt, so that the connection has to be
+    /// closed. Datagrams are always read on their own.
+    pub fn is_broken(&self) -> bool {
+        self.frames.is_broken()
+    }
+
+    fn receive_datagram(&mut self, bytes: &[u8], now: Instant) -> Result<Vec<TransMessage>> {
+        let packet = if Reassembler::is_fragment(bytes) {
+            match self.reassembler.insert(bytes, now)? {
+                Some(whole) => decode_any::<Packet>(&whole)?,
+                None => return Ok(vec![]),
+            }
+        } else {
+            decode_any::<Packet>(bytes)?
+        };
+        Ok(self.endpoint.receive(packet, now))
+    }
+
+    /// Retransmit overdue messages and drop messages whose fragments did not all arrive,
+    /// see `ReliableEndpoint::update` and `Reassembler::expire`.
+    pub fn update(&mut self, now: Instant) -> Result<()> {
+        let expired = self.reassembler.expire(now);
+        self.endpoint.update(now)?;
+        expired
+    }
+
+    /// Whether anything waits to be sent, see `drain_outgoing`.
+    pub fn has_outgoing(&self) -> bool {
+        self.endpoint.has_outgoing()
+    }
+
+    /// The encoded datagrams or frames that must be sent to the remote side.
+    pub fn drain_outgoing(&mut self) -> Vec<Vec<u8>> {
+        let codec = &self.codec;
+        let transport = self.transport;
+        let fragmenter = &mut self.fragmenter;
+        self.endpoint
+            .drain_outgoing()
+            .into_iter()
+            .filter_map(|packet| {
+                let encoded = codec.encode(&packet).and_then(|bytes| match transport {
+                    Transport::Udp => fragmenter.split(bytes),
+                    Transport::Tcp => frame(&bytes).map(|framed| vec![framed]),
+                });
+                match encoded {
+                    Ok(chunks) => Some(chunks),
+                    Err(e) => {
+                        error!("Failed to encode {:?}: {:?}", packet, e);
+                        None
+                    }
+                }
+            })
+            .flatten()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        clientinfo::ClientInfo,
+        msg::{ConnectionMessage, MessageError},
+    };
+
+    fn heartbeat() -> TransMessage {
+        TransMessage::connection(ClientInfo::default(), ConnectionMessage::Heartbeat)
+    }
+
+    #[test]
+    fn messages_before_a_corrupt_frame_are_kept() {
+        let now = Instant::now();
+        let mut sender = Peer::new(CodecKind::Json, Transport::Tcp);
+        let mut stream = vec![];
+        for _ in 0..2 {
+            sender.send(heartbeat(), Delivery::Lossy, now);
+            stream.extend(sender.drain_outgoing().concat());
+            // Skipped, the next frame is read again
+            stream.extend(frame(b"garbage").unwrap());
+        }
+        // Where the frame after this one would start is lost
+        stream.extend(&u32::MAX.to_be_bytes());
+
+        let mut receiver = Peer::new(CodecKind::Json, Transport::Tcp);
+        let mut messages = vec![];
+        let result = receiver.receive(&stream, now, &mut messages);
+        assert!(matches!(result, Err(MessageError::TooLarge)));
+        assert_eq!(messages.len(), 2);
+        assert!(receiver.is_broken());
+    }
+}
@@ -0,0 +1,409 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{hash_map::RandomState, BTreeMap, HashMap},
+    hash::{BuildHasher, Hasher},
+    time::{Duration, Instant},
+};
+
+use crate::msg::{MessageError, MessageLayer, Result, TransMessage};
+
+/// How a message should be delivered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Delivery {
+    /// Acknowledged, retransmitted until acked and delivered in order within its channel.
+    Reliable,
+    /// Sent once, may get lost. Meant for cosmetic traffic.
+    Lossy,
+}
+
+/// Packets this far ahead of the next expected one are dropped without an ack, the sender
+/// sends them again later. It bounds what a channel buffers while a packet is missing.
+pub const MAX_OUT_OF_ORDER: u32 = 256;
+
+/// Every message layer is an independent ordered channel, so a lost chat message does not
+/// hold back the game.
+pub type Channel = MessageLayer;
+
+/// What actually travels over the wire.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Packet {
+    /// Random id of the sending endpoint. A new id means the remote side restarted,
+    /// so all sequence numbers start from the beginning again.
+    pub session: u32,
+    pub kind: PacketKind,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub enum PacketKind {
+    Reliable {
+        channel: Channel,
+        seq: u32,
+        msg: TransMessage,
+    },
+    Lossy(TransMessage),
+    /// Acknowledges the reliable packet `seq` of `channel`.
+    Ack {
+        channel: Channel,
+        seq: u32,
+    },
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ReliableConfig {
+    /// Time until the first retransmission, doubled after every attempt
+    pub initial_timeout: Duration,
+    pub max_timeout: Duration,
+    /// Give up on a packet after it was sent this many times without an ack
+    pub max_retries: u32,
+}
+
+impl Default for ReliableConfig {
+    fn default() -> Self {
+        Self {
+            initial_timeout: Duration::from_millis(200),
+            max_timeout: Duration::from_secs(2),
+            max_retries: 10,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Pending {
+    channel: Channel,
+    seq: u32,
+    msg: TransMessage,
+    next_send: Instant,
+    timeout: Duration,
+    // How often the packet was sent so far
+    attempts: u32,
+}
+
+/// Adds sequence numbers, acks, retransmission, duplicate suppression and ordered delivery
+/// on top of an unreliable datagram channel. It does not do any io itself: everything that
+/// must be sent is collected and handed out by `drain_outgoing`.
+#[derive(Debug)]
+pub struct ReliableEndpoint {
+    config: ReliableConfig,
+    session: u32,
+    remote_session: Option<u32>,
+    next_send_seq: HashMap<Channel, u32>,
+    next_recv_seq: HashMap<Channel, u32>,
+    // Packets that arrived ahead of a missing one
+    out_of_order: HashMap<Channel, BTreeMap<u32, TransMessage>>,
+    pending: Vec<Pending>,
+    outgoing: Vec<Packet>,
+}
+
+impl Default for ReliableEndpoint {
+    fn default() -> Self {
+        Self::new(ReliableConfig::default())
+    }
+}
+
+impl ReliableEndpoint {
+    pub fn new(config: ReliableConfig) -> Self {
+        Self {
+            config,
+            session: RandomState::new().build_hasher().finish() as u32,
+            remote_session: None,
+            next_send_seq: HashMap::default(),
+            next_recv_seq: HashMap::default(),
+            out_of_order: HashMap::default(),
+            pending: Vec::new(),
+            outgoing: Vec::new(),
+        }
+    }
+
+    pub fn send(&mut self, msg: TransMessage, delivery: Delivery, now: Instant) {
+        match delivery {
+            Delivery::Lossy => self.push(PacketKind::Lossy(msg)),
+            Delivery::Reliable => {
+                let channel = msg.layer();
+                let seq = self.next_send_seq.entry(channel).or_insert(0);
+                let packet = PacketKind::Reliable {
+                    channel,
+                    seq: *seq,
+                    msg: msg.clone(),
+                };
+                self.pending.push(Pending {
+                    channel,
+                    seq: *seq,
+                    msg,
+                    next_send: now + self.config.initial_timeout,
+                    timeout: self.config.initial_timeout,
+                    attempts: 1,
+                });
+                *seq += 1;
+                // Send right away instead of waiting for the next update
+                self.push(packet);
+            }
+        }
+    }
+
+    /// Process a packet from the remote side and return the messages that are ready to be
+    /// handled, in order.
+    pub fn receive(&mut self, packet: Packet, _now: Instant) -> Vec<TransMessage> {
+        if self.remote_session != Some(packet.session) {
+            if self.remote_session.is_some() {
+                self.reset();
+            }
+            self.remote_session = Some(packet.session);
+        }
+
+        match packet.kind {
+            PacketKind::Lossy(msg) => vec![msg],
+            PacketKind::Ack { channel, seq } => {
+                self.pending
+                    .retain(|p| !(p.channel == channel && p.seq == seq));
+                vec![]
+            }
+            PacketKind::Reliable { channel, seq, msg } => {
+                let next = *self.next_recv_seq.entry(channel).or_insert(0);
+                if seq >= next.saturating_add(MAX_OUT_OF_ORDER) {
+                    return vec![];
+                }
+                // Always ack, the previous ack may have been lost
+                self.push(PacketKind::Ack { channel, seq });
+                let next = self.next_recv_seq.entry(channel).or_insert(0);
+                if seq < *next {
+                    // Duplicate
+                    return vec![];
+                }
+                let buffer = self.out_of_order.entry(channel).or_default();
+                buffer.insert(seq, msg);
+                let mut ready = vec![];
+                while let Some(msg) = buffer.remove(&*next) {
+                    ready.push(msg);
+                    *next += 1;
+                }
+                ready
+            }
+        }
+    }
+
+    /// Retransmit the packets whose ack is overdue. Fails if a packet was retransmitted too
+    /// often without an ack, which means the remote side is most likely gone. The channel
+    /// cannot go on without the packet, so the connection has to be started over.
+    pub fn update(&mut self, now: Instant) -> Result<()> {
+        let config = self.config;
+        let session = self.session;
+
+        let before = self.pending.len();
+        self.pending
+            .retain(|p| p.next_send > now || p.attempts <= config.max_retries);
+        let result = if self.pending.len() < before {
+            Err(MessageError::Unacknowledged)
+        } else {
+            Ok(())
+        };
+
+        let outgoing = &mut self.outgoing;
+        for p in self.pending.iter_mut().filter(|p| p.next_send <= now) {
+            p.timeout = (p.timeout * 2).min(config.max_timeout);
+            p.attempts += 1;
+            p.next_send = now + p.timeout;
+            outgoing.push(Packet {
+                session,
+                kind: PacketKind::Reliable {
+                    channel: p.channel,
+                    seq: p.seq,
+                    msg: p.msg.clone(),
+                },
+            });
+        }
+        result
+    }
+
+    pub fn drain_outgoing(&mut self) -> Vec<Packet> {
+        self.outgoing.drain(..).collect()
+    }
+
+    /// Whether anything waits to be sent.
+    pub fn has_outgoing(&self) -> bool {
+        !self.outgoing.is_empty()
+    }
+
+    /// Number of reliable packets still waiting for their ack.
+    pub fn unacked(&self) -> usize {
+        self.pending.len()
+    }
+
+    fn push(&mut self, kind: PacketKind) {
+        self.outgoing.push(Packet {
+            session: self.session,
+            kind,
+        });
+    }
+
+    /// The remote side restarted, start over with all channels.
+    fn reset(&mut self) {
+        self.next_send_seq.clear();
+        self.next_recv_seq.clear();
+        self.out_of_order.clear();
+        self.pending.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clientinfo::ClientInfo;
+
+    fn chat(text: &str) -> TransMessage {
+        TransMessage::chat(ClientInfo::default(), text.to_string())
+    }
+
+    fn text(msg: &TransMessage) -> &str {
+        match msg {
+            TransMessage::Chat(m) => &m.body.text,
+            _ => panic!("not a chat message: {:?}", msg),
+        }
+    }
+
+    fn texts(msgs: &[TransMessage]) -> Vec<&str> {
+        msgs.iter().map(text).collect()
+    }
+
+    fn acks(packets: &[Packet]) -> Vec<u32> {
+        packets
+            .iter()
+            .filter_map(|p| match p.kind {
+                PacketKind::Ack { seq, .. } => Some(seq),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Sends `texts` reliably and returns the packets that went out.
+    fn sent(endpoint: &mut ReliableEndpoint, texts: &[&str], now: Instant) -> Vec<Packet> {
+        for t in texts {
+            endpoint.send(chat(t), Delivery::Reliable, now);
+        }
+        endpoint.drain_outgoing()
+    }
+
+    #[test]
+    fn reordered_packets_are_delivered_in_order() {
+        let now = Instant::now();
+        let mut a = ReliableEndpoint::default();
+        let mut b = ReliableEndpoint::default();
+        let packets = sent(&mut a, &["0", "1", "2"], now);
+
+        assert!(b.receive(packets[2].clone(), now).is_empty());
+        assert!(b.receive(packets[1].clone(), now).is_empty());
+        assert_eq!(texts(&b.receive(packets[0].clone(), now)), ["0", "1", "2"]);
+        assert_eq!(acks(&b.drain_outgoing()), [2, 1, 0]);
+    }
+
+    #[test]
+    fn duplicates_are_acked_but_delivered_once() {
+        let now = Instant::now();
+        let mut a = ReliableEndpoint::default();
+        let mut b = ReliableEndpoint::default();
+        let packets = sent(&mut a, &["0", "1"], now);
+
+        assert!(b.receive(packets[1].clone(), now).is_empty());
+        assert!(b.receive(packets[1].clone(), now).is_empty());
+        assert_eq!(texts(&b.receive(packets[0].clone(), now)), ["0", "1"]);
+        assert!(b.receive(packets[0].clone(), now).is_empty());
+        assert_eq!(acks(&b.drain_outgoing()), [1, 1, 0, 0]);
+    }
+
+    #[test]
+    fn acked_packets_are_not_sent_again() {
+        let now = Instant::now();
+        let mut a = ReliableEndpoint::default();
+        let mut b = ReliableEndpoint::default();
+        let packets = sent(&mut a, &["0", "1"], now);
+        b.receive(packets[0].clone(), now);
+        for ack in b.drain_outgoing() {
+            a.receive(ack, now);
+        }
+        assert_eq!(a.unacked(), 1);
+
+        let later = now + Duration::from_secs(1);
+        a.update(later).unwrap();
+        let resent = a.drain_outgoing();
+        assert_eq!(resent.len(), 1);
+        assert_eq!(texts(&b.receive(resent[0].clone(), later)), ["1"]);
+    }
+
+    #[test]
+    fn retransmission_backs_off() {
+        let now = Instant::now();
+        let config = ReliableConfig::default();
+        let mut a = ReliableEndpoint::new(config);
+        sent(&mut a, &["0"], now);
+
+        // Nothing is due before the initial timeout
+        a.update(now + config.initial_timeout / 2).unwrap();
+        assert!(a.drain_outgoing().is_empty());
+        let first = now + config.initial_timeout;
+        a.update(first).unwrap();
+        assert_eq!(a.drain_outgoing().len(), 1);
+        // The timeout doubled
+        a.update(first + config.initial_timeout).unwrap();
+        assert!(a.drain_outgoing().is_empty());
+        a.update(first + config.initial_timeout * 2).unwrap();
+        assert_eq!(a.drain_outgoing().len(), 1);
+    }
+
+    #[test]
+    fn unacknowledged_packets_fail_after_max_retries() {
+        let mut now = Instant::now();
+        let config = ReliableConfig {
+            max_retries: 3,
+            ..ReliableConfig::default()
+        };
+        let mut a = ReliableEndpoint::new(config);
+        sent(&mut a, &["0"], now);
+
+        for _ in 1..config.max_retries {
+            now += config.max_timeout;
+            a.update(now).unwrap();
+        }
+        now += config.max_timeout;
+        a.update(now).unwrap();
+        assert_eq!(a.drain_outgoing().len(), config.max_retries as usize);
+        now += config.max_timeout;
+        assert!(matches!(a.update(now), Err(MessageError::Unacknowledged)));
+        assert_eq!(a.unacked(), 0);
+    }
+
+    #[test]
+    fn packets_too_far_ahead_are_dropped_unacked() {
+        let now = Instant::now();
+        let mut a = ReliableEndpoint::default();
+        let mut b = ReliableEndpoint::default();
+        let mut packets = vec![];
+        for i in 0..=MAX_OUT_OF_ORDER {
+            packets.extend(sent(&mut a, &[&i.to_string()], now));
+        }
+
+        let last = packets.pop().unwrap();
+        assert!(b.receive(last.clone(), now).is_empty());
+        assert!(b.drain_outgoing().is_empty());
+        for p in packets.drain(1..) {
+            assert!(b.receive(p, now).is_empty());
+        }
+        b.drain_outgoing();
+        let ready = b.receive(packets.remove(0), now);
+        assert_eq!(ready.len(), MAX_OUT_OF_ORDER as usize);
+        // Once the gap is filled the packet fits into the window again
+        assert_eq!(texts(&b.receive(last, now)), [MAX_OUT_OF_ORDER.to_string()]);
+    }
+
+    #[test]
+    fn new_remote_session_starts_over() {
+        let now = Instant::now();
+        let mut a = ReliableEndpoint::default();
+        let mut b = ReliableEndpoint::default();
+        for p in sent(&mut a, &["0", "1"], now) {
+            b.receive(p, now);
+        }
+
+        let mut restarted = ReliableEndpoint::default();
+        let packets = sent(&mut restarted, &["again"], now);
+        assert_eq!(texts(&b.receive(packets[0].clone(), now)), ["again"]);
+    }
+}
@@ -0,0 +1,165 @@
+use std::{
+    convert::TryInto,
+    fmt::{self, Display, Formatter},
+    str::FromStr,
+};
+
+use crate::msg::{MessageError, Result};
+
+/// Length of the prefix in front of every frame on a stream.
+const LENGTH_PREFIX: usize = 4;
+/// Anything bigger is considered a corrupt stream.
+const MAX_FRAME: usize = 1 << 20;
+
+/// The kind of socket all traffic between a client and the server goes over.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transport {
+    /// Datagrams, made reliable by the `reliable` layer.
+    Udp,
+    /// A stream with length-prefixed frames, for networks that block udp.
+    Tcp,
+}
+
+impl FromStr for Transport {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "udp" => Ok(Transport::Udp),
+            "tcp" => Ok(Transport::Tcp),
+            _ => Err(format!("Unknown transport {}, expected tcp or udp", s)),
+        }
+    }
+}
+
+impl Display for Transport {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Transport::Udp => write!(f, "udp"),
+            Transport::Tcp => write!(f, "tcp"),
+        }
+    }
+}
+
+/// Put the length in front of an encoded message, so it can be found again in a stream.
+pub fn frame(bytes: &[u8]) -> Result<Vec<u8>> {
+    if bytes.len() > MAX_FRAME {
+        return Err(MessageError::TooLarge);
+    }
+    let mut framed = Vec::with_capacity(LENGTH_PREFIX + bytes.len());
+    framed.extend_from_slice(&(bytes.len() as u32).to_be_bytes());
+    framed.extend_from_slice(bytes);
+    Ok(framed)
+}
+
+/// Collects the bytes read from a stream and cuts them into frames again. A read may
+/// contain part of a frame or several frames at once.
+#[derive(Debug, Default)]
+pub struct FrameDecoder {
+    buffer: Vec<u8>,
+    // A length was out of bounds, where the next frame starts is lost
+    broken: bool,
+}
+
+impl FrameDecoder {
+    pub fn extend(&mut self, bytes: &[u8]) {
+        if !self.broken {
+            self.buffer.extend_from_slice(bytes);
+        }
+    }
+
+    /// Whether the stream is corrupt. Nothing can be read from it anymore, the connection
+    /// has to be closed.
+    pub fn is_broken(&self) -> bool {
+        self.broken
+    }
+
+    /// The next complete frame, if there is one.
+    pub fn next_frame(&mut self) -> Result<Option<Vec<u8>>> {
+        if self.broken {
+            return Err(MessageError::TooLarge);
+        }
+        if self.buffer.len() < LENGTH_PREFIX {
+            return Ok(None);
+        }
+        let len = u32::from_be_bytes(self.buffer[..LENGTH_PREFIX].try_into().unwrap()) as usize;
+        if len > MAX_FRAME {
+            self.buffer.clear();
+            self.broken = true;
+            return Err(MessageError::TooLarge);
+        }
+        if self.buffer.len() < LENGTH_PREFIX + len {
+            return Ok(None);
+        }
+        let frame = self.buffer[LENGTH_PREFIX..LENGTH_PREFIX + len].to_vec();
+        self.buffer.drain(..LENGTH_PREFIX + len);
+        Ok(Some(frame))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frames(decoder: &mut FrameDecoder) -> Vec<Vec<u8>> {
+        let mut frames = vec![];
+        while let Some(frame) = decoder.next_frame().unwrap() {
+            frames.push(frame);
+        }
+        frames
+    }
+
+    #[test]
+    fn frame_split_over_reads() {
+        let framed = frame(b"hello world").unwrap();
+        let mut decoder = FrameDecoder::default();
+        // Part of the length prefix, then part of the message
+        decoder.extend(&framed[..2]);
+        assert!(frames(&mut decoder).is_empty());
+        decoder.extend(&framed[2..8]);
+        assert!(frames(&mut decoder).is_empty());
+        decoder.extend(&framed[8..]);
+        assert_eq!(frames(&mut decoder), [b"hello world".to_vec()]);
+    }
+
+    #[test]
+    fn frames_coalesced_in_one_read() {
+        let mut stream = frame(b"first").unwrap();
+        stream.extend(frame(b"").unwrap());
+        stream.extend(frame(b"third").unwrap());
+        let last = frame(b"fourth").unwrap();
+        stream.extend_from_slice(&last[..3]);
+
+        let mut decoder = FrameDecoder::default();
+        decoder.extend(&stream);
+        assert_eq!(
+            frames(&mut decoder),
+            [b"first".to_vec(), vec![], b"third".to_vec()]
+        );
+        decoder.extend(&last[3..]);
+        assert_eq!(frames(&mut decoder), [b"fourth".to_vec()]);
+    }
+
+    #[test]
+    fn oversized_frames_are_refused() {
+        assert!(matches!(
+            frame(&vec![0; MAX_FRAME + 1]),
+            Err(MessageError::TooLarge)
+        ));
+        let mut decoder = FrameDecoder::default();
+        decoder.extend(&(MAX_FRAME as u32 + 1).to_be_bytes());
+        assert!(matches!(decoder.next_frame(), Err(MessageError::TooLarge)));
+        // Where the next frame starts is unknown, the stream stays broken
+        assert!(decoder.is_broken());
+        decoder.extend(&frame(b"next").unwrap());
+        assert!(matches!(decoder.next_frame(), Err(MessageError::TooLarge)));
+    }
+
+    #[test]
+    fn transport_names() {
+        assert_eq!("UDP".parse(), Ok(Transport::Udp));
+        assert_eq!("tcp".parse(), Ok(Transport::Tcp));
+        assert!("quic".parse::<Transport>().is_err());
+        assert_eq!(Transport::Tcp.to_string(), "tcp");
+    }
+}
@@ -1,6 +1,6 @@
 #!/bin/bash
 
-cargo build --release
+cargo build --release -p server
 
 cp ./target/release/server ./server/
 